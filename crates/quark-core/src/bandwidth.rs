@@ -0,0 +1,113 @@
+use crate::limiter::TokenBucket;
+use crate::settings::BandwidthSettings;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// 限速下限：过低的速率会让单个数据块等待数十秒，暂停 / 取消都跟着变慢
+const MIN_RATE: u64 = 64 * 1024;
+
+/// 时段规则的检查间隔
+const SCHEDULE_TICK: Duration = Duration::from_secs(30);
+
+fn effective_rate(limit: Option<u64>) -> u64 {
+    match limit {
+        Some(0) | None => 0,
+        Some(rate) => rate.max(MIN_RATE),
+    }
+}
+
+/// 全局带宽限制：所有下载共享一个全局令牌桶，每个下载另有自己的桶。
+/// 设置可在运行中替换，时段规则由后台任务定期重新计算。
+pub struct BandwidthLimit {
+    settings: Mutex<BandwidthSettings>,
+    /// 当前生效的全局限速（已按时段计算），0 表示不限
+    global_rate: AtomicU64,
+    per_download_rate: AtomicU64,
+    global: TokenBucket,
+}
+
+impl BandwidthLimit {
+    pub fn new(settings: BandwidthSettings) -> Self {
+        let limit = Self {
+            settings: Mutex::new(BandwidthSettings::default()),
+            global_rate: AtomicU64::new(0),
+            per_download_rate: AtomicU64::new(0),
+            global: TokenBucket::new(),
+        };
+        limit.apply(settings);
+        limit
+    }
+
+    /// 替换限速设置，对正在进行的下载立即生效
+    pub fn apply(&self, settings: BandwidthSettings) {
+        let per_download = effective_rate(settings.per_download_limit);
+        if let Ok(mut current) = self.settings.lock() {
+            *current = settings;
+        }
+        self.per_download_rate
+            .store(per_download, Ordering::Relaxed);
+        self.refresh();
+    }
+
+    /// 按当前本地时间重新计算全局限速
+    pub fn refresh(&self) {
+        let now = chrono::Local::now().time();
+        let Some(limit) = self.settings.lock().ok().map(|s| s.global_limit_at(now)) else {
+            return;
+        };
+        let rate = effective_rate(limit);
+        let previous = self.global_rate.swap(rate, Ordering::Relaxed);
+        if previous != rate {
+            self.global.reset();
+            match rate {
+                0 => log!("[bandwidth] 全局限速: 不限"),
+                _ => log!(
+                    "[bandwidth] 全局限速: {:.1} MB/s",
+                    rate as f64 / 1024.0 / 1024.0
+                ),
+            }
+        }
+    }
+
+    /// 定期检查时段规则，跨过时段边界时切换全局限速；由调用方放到后台任务中运行
+    pub async fn run_schedule(self: Arc<Self>) {
+        loop {
+            tokio::time::sleep(SCHEDULE_TICK).await;
+            self.refresh();
+        }
+    }
+
+    /// 为一次下载创建限速器
+    pub fn throttle(self: &Arc<Self>) -> Arc<DownloadThrottle> {
+        Arc::new(DownloadThrottle {
+            limit: Arc::clone(self),
+            bucket: TokenBucket::new(),
+        })
+    }
+}
+
+/// 单个下载的限速器，同时受全局桶与本下载的桶约束，所有分段共享
+pub struct DownloadThrottle {
+    limit: Arc<BandwidthLimit>,
+    bucket: TokenBucket,
+}
+
+impl DownloadThrottle {
+    /// 收到 bytes 字节后调用，超出限速时在这里等待
+    pub async fn consume(&self, bytes: usize) {
+        let bytes = bytes as f64;
+        let own = self.bucket.reserve(
+            bytes,
+            self.limit.per_download_rate.load(Ordering::Relaxed) as f64,
+        );
+        let global = self
+            .limit
+            .global
+            .reserve(bytes, self.limit.global_rate.load(Ordering::Relaxed) as f64);
+        let wait = own.max(global);
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
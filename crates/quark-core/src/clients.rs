@@ -0,0 +1,169 @@
+use crate::limiter::TokenBucket;
+use crate::proxy::{self, ProxyTarget};
+use crate::settings::{NetworkSettings, Settings};
+use rand::Rng;
+use reqwest::{redirect, Client};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::Duration;
+
+const RETRY_BASE_DELAY_MS: u64 = 500;
+const RETRY_MAX_DELAY_MS: u64 = 8_000;
+/// Retry-After 超过这个值就不再等，直接把限流错误交给调用方
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// 应用级共享的 HTTP 客户端：夸克 API 与 CDN 下载各一个。
+/// reqwest::Client 内部是 Arc 包着的连接池，clone 代价很低，同一主机的后续请求复用已建立的连接与 TLS 会话。
+/// 代理或网络设置变化时整体重建，进行中的请求继续用旧客户端直到结束。
+pub struct HttpClients {
+    api: RwLock<ApiClient>,
+    cdn: RwLock<Client>,
+}
+
+impl HttpClients {
+    pub fn new(settings: &Settings) -> Result<Self, String> {
+        proxy::log_routes(&settings.proxy);
+        Ok(Self {
+            api: RwLock::new(ApiClient::new(settings)?),
+            cdn: RwLock::new(build_cdn(settings)?),
+        })
+    }
+
+    /// 夸克 API、扫码登录使用的客户端
+    pub fn api(&self) -> ApiClient {
+        self.api
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// CDN 下载使用的客户端
+    pub fn cdn(&self) -> Client {
+        self.cdn
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// 代理或网络设置变化后重建两个客户端
+    pub fn configure(&self, settings: &Settings) -> Result<(), String> {
+        let api = ApiClient::new(settings)?;
+        let cdn = build_cdn(settings)?;
+        *self.api.write().unwrap_or_else(PoisonError::into_inner) = api;
+        *self.cdn.write().unwrap_or_else(PoisonError::into_inner) = cdn;
+        proxy::log_routes(&settings.proxy);
+        Ok(())
+    }
+}
+
+/// 夸克 API 客户端：连接池 + 重试策略 + 按主机的请求速率限制
+#[derive(Clone)]
+pub struct ApiClient {
+    http: Client,
+    max_retries: u32,
+    hosts: Arc<HostRateLimit>,
+}
+
+impl ApiClient {
+    fn new(settings: &Settings) -> Result<Self, String> {
+        Ok(Self {
+            http: build_api(settings)?,
+            max_retries: settings.network.max_retries,
+            hosts: Arc::new(HostRateLimit::new(settings.network.requests_per_second)),
+        })
+    }
+
+    pub fn http(&self) -> &Client {
+        &self.http
+    }
+
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    /// 按主机限速：同一主机的请求超过速率上限时在这里排队
+    pub async fn wait_turn(&self, host: &str) {
+        let wait = self.hosts.reserve(host);
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// 每个主机一个令牌桶
+struct HostRateLimit {
+    rate: f64,
+    buckets: Mutex<HashMap<String, Arc<TokenBucket>>>,
+}
+
+impl HostRateLimit {
+    fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn reserve(&self, host: &str) -> Duration {
+        if self.rate <= 0.0 {
+            return Duration::ZERO;
+        }
+        let bucket = {
+            let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+            Arc::clone(buckets.entry(host.to_string()).or_default())
+        };
+        bucket.reserve(1.0, self.rate)
+    }
+}
+
+/// 第 n 次重试前的等待时间：指数退避（封顶）加 [50%, 100%] 的随机抖动
+pub fn retry_delay(attempt: u32) -> Duration {
+    let exp = RETRY_BASE_DELAY_MS.saturating_mul(1u64 << (attempt.max(1) - 1).min(16));
+    let capped = exp.min(RETRY_MAX_DELAY_MS);
+    Duration::from_millis(rand::thread_rng().gen_range(capped / 2..=capped))
+}
+
+/// 解析 Retry-After 头（只支持秒数形式）
+pub fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// API 请求体量小但数量多（解析大分享时成百上千页），多保留一些空闲连接。
+/// 用读超时而不是整体超时：慢但持续有数据的响应不会被误杀，挂起的连接会被及时断开
+fn build_api(settings: &Settings) -> Result<Client, String> {
+    let NetworkSettings {
+        connect_timeout_secs,
+        read_timeout_secs,
+        ..
+    } = settings.network;
+    proxy::apply(Client::builder(), &settings.proxy, ProxyTarget::Api)
+        .redirect(redirect::Policy::none())
+        .connect_timeout(Duration::from_secs(connect_timeout_secs))
+        .read_timeout(Duration::from_secs(read_timeout_secs))
+        .tcp_keepalive(Duration::from_secs(60))
+        .pool_idle_timeout(Duration::from_secs(90))
+        .pool_max_idle_per_host(16)
+        .build()
+        .map_err(|e| format!("创建 API 客户端失败: {}", e))
+}
+
+/// CDN 下载不设整体超时（大文件要下很久）；分段连接多，空闲连接上限放宽。
+/// 重定向由下载引擎自己跟踪，以便每一跳都带上 Cookie
+fn build_cdn(settings: &Settings) -> Result<Client, String> {
+    proxy::apply(Client::builder(), &settings.proxy, ProxyTarget::Cdn)
+        .redirect(redirect::Policy::none())
+        .connect_timeout(Duration::from_secs(30))
+        .tcp_keepalive(Duration::from_secs(60))
+        .pool_idle_timeout(Duration::from_secs(90))
+        .pool_max_idle_per_host(64)
+        .http1_only()
+        .build()
+        .map_err(|e| format!("创建下载客户端失败: {}", e))
+}
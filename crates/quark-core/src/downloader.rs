@@ -0,0 +1,1154 @@
+use crate::bandwidth::DownloadThrottle;
+use crate::limiter::ConcurrencyLimit;
+use crate::progress::{DownloadProgress, ProgressReporter, ProgressStatus, SegmentRetries};
+use crate::registry::{DownloadControl, DownloadHandle};
+use crate::resume;
+use crate::sanitize;
+use crate::scheduler::{self, Block, Scheduler};
+use crate::settings::ConflictPolicy;
+use md5::{Digest, Md5};
+use rand::Rng;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+
+pub const UA: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) quark-cloud-drive/2.5.20 Chrome/100.0.4896.160 Electron/18.3.5.4-b478491100 Safari/537.36 Channel/pckk_other_ch";
+const MIN_MULTITHREAD_SIZE: u64 = 10 * 1024 * 1024; // 10MB 以下走单线程
+pub const DEFAULT_SEGMENT_RETRIES: u32 = 5;
+pub const MAX_SEGMENT_RETRIES: u32 = 50;
+const RETRY_BASE_DELAY_MS: u64 = 1000;
+const RETRY_MAX_DELAY_MS: u64 = 30_000;
+const SEGMENT_BUFFER_SIZE: usize = 1024 * 1024; // 每段攒满 1MB 再落盘
+const REPAIR_ROUNDS: u32 = 2; // 校验发现缺失区间后最多补下的轮数
+
+// ── 通用工具 ──────────────────────────────────────────────
+
+pub fn add_headers(req: reqwest::RequestBuilder, cookie: &str) -> reqwest::RequestBuilder {
+    req.header("User-Agent", UA)
+        .header("Cookie", cookie)
+        .header("Referer", "https://pan.quark.cn/")
+        .header("Accept", "*/*")
+        .header("Accept-Language", "zh-CN,zh;q=0.9")
+        .header("Accept-Encoding", "identity")
+        .header("Connection", "keep-alive")
+}
+
+/// 手动跟随 302 重定向，返回 (最终URL, 响应)
+pub async fn follow_redirects(
+    client: &reqwest::Client,
+    url: &str,
+    cookie: &str,
+) -> Result<(String, reqwest::Response), String> {
+    let mut current_url = url.to_string();
+    let mut redirects = 0u32;
+
+    loop {
+        if redirects > 5 {
+            return Err("重定向次数过多".into());
+        }
+
+        let resp = add_headers(client.get(&current_url), cookie)
+            .send()
+            .await
+            .map_err(|e| format!("请求失败: {}", e))?;
+
+        if resp.status().is_redirection() {
+            if let Some(loc) = resp.headers().get("location") {
+                let next = loc.to_str().map_err(|e| e.to_string())?;
+                current_url = url::Url::parse(&current_url)
+                    .and_then(|base| base.join(next))
+                    .map(|u| u.to_string())
+                    .unwrap_or_else(|_| next.to_string());
+                log!(
+                    "[download] 重定向 #{}: {}...",
+                    redirects + 1,
+                    &current_url[..current_url.len().min(80)]
+                );
+                redirects += 1;
+                continue;
+            }
+        }
+
+        return Ok((current_url, resp));
+    }
+}
+
+/// 解决文件名冲突：存在同名文件时追加 (1), (2), ...（加后缀后仍不超过文件名长度上限）
+pub fn resolve_save_path(downloads_dir: &Path, filename: &str) -> PathBuf {
+    let save_path = sanitize::long_path(&downloads_dir.join(filename));
+    if !save_path.exists() {
+        return save_path;
+    }
+    let mut i = 1;
+    loop {
+        let new_name = sanitize::with_suffix(filename, &format!(" ({})", i));
+        let save_path = sanitize::long_path(&downloads_dir.join(new_name));
+        if !save_path.exists() {
+            return save_path;
+        }
+        i += 1;
+    }
+}
+
+/// 系统下载目录，取不到时用当前目录
+pub fn default_download_dir() -> PathBuf {
+    dirs::download_dir().unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// 下载根目录：指定了非空目录则用之，否则用系统下载目录
+pub fn resolve_root(dir: Option<&str>) -> PathBuf {
+    dir.map(str::trim)
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(default_download_dir)
+}
+
+/// 把分享内的相对路径（如 season1/ep01.mkv）映射到本地：root[/分享标题]/season1，
+/// 各级名称逐个清洗，含 ".." 的路径直接拒绝。
+/// 返回 (保存目录, 文件名)；相对路径为空时文件名用 fallback_name
+pub fn share_target(
+    root: &Path,
+    share_title: Option<&str>,
+    rel_path: Option<&str>,
+    fallback_name: &str,
+) -> Result<(PathBuf, String), String> {
+    let mut dir = root.to_path_buf();
+    if let Some(title) = share_title.filter(|t| !t.trim().is_empty()) {
+        dir.push(sanitize::sanitize_filename(title));
+    }
+    let mut parts = sanitize::sanitize_relative_path(rel_path.unwrap_or(""))?;
+    let name = parts
+        .pop()
+        .unwrap_or_else(|| sanitize::sanitize_filename(fallback_name));
+    dir.extend(parts);
+    Ok((dir, name))
+}
+
+/// 确定保存路径与续传状态文件：存在上次未完成的部分数据时沿用其保存路径；
+/// 否则 Rename 策略按重名规则分配新路径，其余策略先占用原文件名，
+/// 拿到远端大小后再由 resolve_conflict 决定覆盖、跳过还是另存
+pub async fn prepare_target(
+    downloads_dir: &Path,
+    filename: &str,
+    policy: ConflictPolicy,
+) -> (PathBuf, PathBuf) {
+    let state_path = resume::state_path(downloads_dir, filename);
+    let save_path = match resume::DownloadState::load(&state_path).await {
+        Some(state) if state.save_path.exists() => state.save_path,
+        _ if policy == ConflictPolicy::Rename => resolve_save_path(downloads_dir, filename),
+        _ => sanitize::long_path(&downloads_dir.join(filename)),
+    };
+    (save_path, state_path)
+}
+
+/// 把 32 位十六进制或 base64 形式的 MD5 统一成小写十六进制
+pub fn normalize_md5(raw: &str) -> Option<String> {
+    use base64::Engine;
+    let raw = raw.trim();
+    if raw.len() == 32 && raw.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Some(raw.to_ascii_lowercase());
+    }
+    let bytes = base64::engine::general_purpose::STANDARD.decode(raw).ok()?;
+    (bytes.len() == 16).then(|| bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// 计算本地文件的 MD5（小写十六进制），在阻塞线程池中执行
+pub async fn file_md5(path: &Path) -> Result<String, String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        use std::io::Read;
+        let mut file = std::fs::File::open(&path).map_err(|e| format!("打开文件失败: {}", e))?;
+        let mut hasher = Md5::new();
+        let mut buf = vec![0u8; 1024 * 1024];
+        loop {
+            let n = file
+                .read(&mut buf)
+                .map_err(|e| format!("读取文件失败: {}", e))?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect())
+    })
+    .await
+    .map_err(|e| format!("计算 MD5 失败: {}", e))?
+}
+
+/// 打开目标文件并预分配到完整大小（已是该大小的部分文件保持原样，用于续传）
+async fn open_preallocated(path: &Path, total_size: u64) -> Result<Arc<std::fs::File>, String> {
+    let file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(path)
+        .await
+        .map_err(|e| format!("创建目标文件失败: {}", e))?;
+    let len = file
+        .metadata()
+        .await
+        .map_err(|e| format!("读取目标文件失败: {}", e))?
+        .len();
+    if len != total_size {
+        file.set_len(total_size)
+            .await
+            .map_err(|e| format!("预分配磁盘空间失败: {}", e))?;
+    }
+    Ok(Arc::new(file.into_std().await))
+}
+
+/// 在阻塞线程池中把缓冲区写到文件的指定偏移，写完交还缓冲区以便复用
+async fn write_at(
+    file: &Arc<std::fs::File>,
+    buf: Vec<u8>,
+    offset: u64,
+) -> std::io::Result<Vec<u8>> {
+    let file = Arc::clone(file);
+    tokio::task::spawn_blocking(move || {
+        write_all_at(&file, &buf, offset)?;
+        let mut buf = buf;
+        buf.clear();
+        Ok(buf)
+    })
+    .await
+    .map_err(std::io::Error::other)?
+}
+
+/// 定位写入（pwrite / seek_write），多个分段共享同一个文件句柄也互不干扰
+fn write_all_at(file: &std::fs::File, buf: &[u8], offset: u64) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileExt;
+        file.write_all_at(buf, offset)
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::FileExt;
+        let (mut buf, mut offset) = (buf, offset);
+        while !buf.is_empty() {
+            let n = file.seek_write(buf, offset)?;
+            if n == 0 {
+                return Err(std::io::ErrorKind::WriteZero.into());
+            }
+            buf = &buf[n..];
+            offset += n as u64;
+        }
+        Ok(())
+    }
+}
+
+// ── 下载调度 ─────────────────────────────────────────────
+
+/// 单次下载的上下文，在调度的各个阶段之间传递
+pub struct DownloadCtx {
+    pub progress: Arc<dyn ProgressReporter>,
+    /// 共享的 CDN 客户端，同一主机的分段请求复用连接
+    pub client: reqwest::Client,
+    pub cookie: String,
+    pub filename: String,
+    /// 来源文件 fid，写入续传状态用于识别同一文件
+    pub fid: String,
+    pub save_path: PathBuf,
+    /// 续传状态文件
+    pub state_path: PathBuf,
+    /// 注册表中的控制句柄（暂停/恢复/取消）
+    pub handle: Arc<DownloadHandle>,
+    pub download_id: String,
+    pub thread_count: usize,
+    /// 每个分段允许的最大重试次数
+    pub max_retries: u32,
+    /// 全局连接数上限，所有下载共享
+    pub connections: Arc<ConcurrencyLimit>,
+    /// 带宽限制（全局 + 本下载）
+    pub throttle: Arc<DownloadThrottle>,
+    /// 目标位置已有文件时的处理方式
+    pub conflict: ConflictPolicy,
+    /// 远端文件 MD5（小写十六进制），SkipSameHash 用
+    pub remote_md5: Option<String>,
+}
+
+/// resolve_conflict 的结论
+enum Conflict {
+    /// 按常规流程下载（含续传自己留下的部分数据）
+    Proceed,
+    /// 已有相同文件，跳过
+    Skip,
+    /// 已有文件是远端的前缀，从该偏移继续
+    ResumeFrom(u64),
+}
+
+/// 拿到远端大小后，按冲突策略处理目标位置上已存在的文件。
+/// 与续传状态匹配的部分数据是本程序上次留下的，任何策略下都直接续传。
+async fn resolve_conflict(
+    ctx: &mut DownloadCtx,
+    total_size: u64,
+    accept_ranges: bool,
+) -> Result<Conflict, String> {
+    if let Some(state) = resume::DownloadState::load(&ctx.state_path).await {
+        if state.matches(&ctx.fid, total_size) && state.save_path == ctx.save_path {
+            return Ok(Conflict::Proceed);
+        }
+    }
+    let Ok(meta) = tokio::fs::metadata(&ctx.save_path).await else {
+        return Ok(Conflict::Proceed);
+    };
+    let existing = meta.len();
+
+    let same_content = match ctx.conflict {
+        ConflictPolicy::SkipSameSize => existing == total_size,
+        ConflictPolicy::SkipSameHash => {
+            existing == total_size
+                && match &ctx.remote_md5 {
+                    Some(remote) => file_md5(&ctx.save_path).await? == *remote,
+                    None => {
+                        log!(
+                            "[download] 远端未提供 MD5，按大小判断是否相同: {}",
+                            ctx.filename
+                        );
+                        true
+                    }
+                }
+        }
+        _ => false,
+    };
+
+    let decision = match ctx.conflict {
+        ConflictPolicy::SkipSameSize | ConflictPolicy::SkipSameHash if same_content => {
+            Conflict::Skip
+        }
+        ConflictPolicy::Resume if existing == total_size => Conflict::Skip,
+        ConflictPolicy::Resume if existing < total_size && accept_ranges => {
+            Conflict::ResumeFrom(existing)
+        }
+        ConflictPolicy::Overwrite | ConflictPolicy::Resume => Conflict::Proceed,
+        _ => {
+            let dir = ctx
+                .save_path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+            let name = ctx
+                .save_path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| ctx.filename.clone());
+            ctx.save_path = resolve_save_path(&dir, &name);
+            Conflict::Proceed
+        }
+    };
+    // 不再沿用与目标文件无关的旧续传状态
+    if tokio::fs::try_exists(&ctx.state_path)
+        .await
+        .unwrap_or(false)
+    {
+        let _ = tokio::fs::remove_file(&ctx.state_path).await;
+    }
+    match decision {
+        Conflict::Skip => log!("[download] 已存在相同文件，跳过: {:?}", ctx.save_path),
+        Conflict::ResumeFrom(offset) => log!(
+            "[download] 已有文件 {:.1}/{:.1} MB，从末尾续传: {:?}",
+            offset as f64 / 1024.0 / 1024.0,
+            total_size as f64 / 1024.0 / 1024.0,
+            ctx.save_path
+        ),
+        Conflict::Proceed => log!(
+            "[download] 目标位置: {:?} (策略 {:?})",
+            ctx.save_path,
+            ctx.conflict
+        ),
+    }
+    Ok(decision)
+}
+
+/// 多线程续传已有的前缀：把文件扩到完整大小，并生成前 offset 字节已完成的续传状态
+async fn seed_resume_state(ctx: &DownloadCtx, total_size: u64, offset: u64) -> Result<(), String> {
+    let file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(&ctx.save_path)
+        .await
+        .map_err(|e| format!("打开已有文件失败: {}", e))?;
+    file.set_len(total_size)
+        .await
+        .map_err(|e| format!("扩展已有文件失败: {}", e))?;
+    let segments = Scheduler::plan(total_size, ctx.thread_count)
+        .into_iter()
+        .map(|mut seg| {
+            seg.written = offset.saturating_sub(seg.start).min(seg.len());
+            seg
+        })
+        .collect();
+    resume::DownloadState::new(
+        &ctx.fid,
+        &ctx.filename,
+        &ctx.save_path,
+        total_size,
+        segments,
+    )
+    .save(&ctx.state_path)
+    .await
+}
+
+/// 跳过下载时的返回值，同样推送一次 done 事件
+fn skipped_result(ctx: &DownloadCtx, total_size: u64) -> serde_json::Value {
+    let mut result = finished_result(ctx, total_size);
+    result["skipped"] = true.into();
+    result
+}
+
+/// 下载（含校验）完成：推送最终进度并返回保存位置
+fn finished_result(ctx: &DownloadCtx, total_size: u64) -> serde_json::Value {
+    ctx.progress.report(DownloadProgress::new(
+        &ctx.download_id,
+        &ctx.filename,
+        total_size,
+        total_size,
+        ProgressStatus::Done,
+    ));
+    serde_json::json!({
+        "path": ctx.save_path.to_string_lossy(),
+        "size": total_size,
+    })
+}
+
+/// 下载结束后的最终校验：磁盘上的文件大小必须等于远端大小；
+/// 有 MD5（夸克文件元数据或 CDN 的 Content-MD5）时再比对内容。
+/// 整文件 MD5 无法定位出错的区间，不一致时删除文件，由调用方整体重下。
+async fn verify_download(
+    ctx: &DownloadCtx,
+    total_size: u64,
+    expected_md5: Option<&str>,
+) -> Result<(), String> {
+    let on_disk = tokio::fs::metadata(&ctx.save_path)
+        .await
+        .map(|m| m.len())
+        .map_err(|e| format!("读取文件信息失败: {}", e))?;
+    if total_size > 0 && on_disk != total_size {
+        return Err(format!(
+            "文件大小不符: 期望 {} bytes，实际 {} bytes",
+            total_size, on_disk
+        ));
+    }
+
+    let Some(expected) = expected_md5 else {
+        return Ok(());
+    };
+    ctx.progress.report(DownloadProgress::new(
+        &ctx.download_id,
+        &ctx.filename,
+        on_disk,
+        total_size,
+        ProgressStatus::Verifying,
+    ));
+    let actual = file_md5(&ctx.save_path).await?;
+    if actual != expected {
+        let _ = tokio::fs::remove_file(&ctx.save_path).await;
+        return Err(format!(
+            "MD5 校验失败: 期望 {}，实际 {}，已删除损坏的文件",
+            expected, actual
+        ));
+    }
+    log!("[download] MD5 校验通过: {}", ctx.filename);
+    Ok(())
+}
+
+/// 从 offset 起请求剩余部分，必须返回 206
+async fn request_range(
+    client: &reqwest::Client,
+    url: &str,
+    cookie: &str,
+    offset: u64,
+) -> Result<reqwest::Response, String> {
+    let resp = add_headers(client.get(url), cookie)
+        .header("Range", format!("bytes={}-", offset))
+        .send()
+        .await
+        .map_err(|e| format!("请求失败: {}", e))?;
+    if resp.status().as_u16() != 206 {
+        return Err(format!("续传请求未返回 206: {}", resp.status()));
+    }
+    Ok(resp)
+}
+
+pub async fn do_download(mut ctx: DownloadCtx, url: String) -> Result<serde_json::Value, String> {
+    if ctx.handle.is_cancelled() {
+        return Err("下载已取消".into());
+    }
+
+    log!(
+        "[download] 开始: {} -> {:?} (id={})",
+        ctx.filename,
+        ctx.save_path,
+        ctx.download_id
+    );
+
+    let client = ctx.client.clone();
+    let (final_url, resp) = follow_redirects(&client, &url, &ctx.cookie).await?;
+
+    if ctx.handle.is_cancelled() {
+        return Err("下载已取消".into());
+    }
+
+    if resp.status().as_u16() >= 400 {
+        let status = resp.status().as_u16();
+        let err = resp.text().await.unwrap_or_default();
+        return Err(format!(
+            "CDN 返回错误 {}: {}",
+            status,
+            &err[..err.len().min(200)]
+        ));
+    }
+
+    let total_size: u64 = resp
+        .headers()
+        .get("content-length")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0);
+
+    let accept_ranges = resp
+        .headers()
+        .get("accept-ranges")
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.contains("bytes"))
+        .unwrap_or(false);
+
+    // 优先用夸克文件元数据里的 MD5，没有时退回 CDN 整文件响应的 Content-MD5
+    let expected_md5 = ctx.remote_md5.clone().or_else(|| {
+        resp.headers()
+            .get("content-md5")
+            .and_then(|v| v.to_str().ok())
+            .and_then(normalize_md5)
+    });
+
+    log!(
+        "[download] CDN 200, 大小: {} ({:.1} MB), 支持 Range: {}",
+        total_size,
+        total_size as f64 / 1024.0 / 1024.0,
+        accept_ranges
+    );
+
+    let offset = match resolve_conflict(&mut ctx, total_size, accept_ranges).await? {
+        Conflict::Skip => return Ok(skipped_result(&ctx, total_size)),
+        Conflict::ResumeFrom(offset) => offset,
+        Conflict::Proceed => 0,
+    };
+
+    if accept_ranges && total_size >= MIN_MULTITHREAD_SIZE {
+        drop(resp);
+        if offset > 0 {
+            seed_resume_state(&ctx, total_size, offset).await?;
+        }
+        download_multithread(&ctx, client, final_url, total_size).await?;
+    } else {
+        log!("[download] 使用单线程下载 (Range 不支持或文件较小)");
+        // 无法分段续传，残留的部分数据已无用
+        if tokio::fs::try_exists(&ctx.state_path)
+            .await
+            .unwrap_or(false)
+        {
+            let _ = tokio::fs::remove_file(&ctx.state_path).await;
+        }
+        let mut resp = if offset > 0 {
+            drop(resp);
+            request_range(&client, &final_url, &ctx.cookie, offset).await?
+        } else {
+            resp
+        };
+        let mut offset = offset;
+        let mut attempt: u32 = 0;
+        loop {
+            let written = download_single(&ctx, resp, total_size, offset).await?;
+            // 服务端提前断开时流会正常结束，只能靠字节数发现截断
+            if total_size == 0 || written == total_size {
+                break;
+            }
+            if written > total_size || !accept_ranges || attempt >= ctx.max_retries {
+                let _ = tokio::fs::remove_file(&ctx.save_path).await;
+                return Err(format!(
+                    "文件不完整: 收到 {}/{} bytes，已删除",
+                    written, total_size
+                ));
+            }
+            attempt += 1;
+            log!(
+                "[download] 连接提前关闭 ({}/{} bytes)，第 {}/{} 次补下剩余部分",
+                written,
+                total_size,
+                attempt,
+                ctx.max_retries
+            );
+            offset = written;
+            resp = request_range(&client, &final_url, &ctx.cookie, offset).await?;
+        }
+    }
+
+    verify_download(&ctx, total_size, expected_md5.as_deref()).await?;
+    Ok(finished_result(&ctx, total_size))
+}
+
+/// 把调度器当前的块划分与进度写回状态快照
+fn checkpoint(state: &resume::DownloadState, scheduler: &Scheduler) -> resume::DownloadState {
+    let mut snapshot = state.clone();
+    snapshot.segments = scheduler.snapshot();
+    snapshot
+}
+
+/// 汇总发生过重试的块，附在 download-progress 事件中
+fn retry_report(scheduler: &Scheduler) -> (u32, Vec<SegmentRetries>) {
+    let mut total = 0;
+    let mut segments = Vec::new();
+    for (i, block) in scheduler.blocks().iter().enumerate() {
+        let retries = block.retries.load(Ordering::Relaxed);
+        if retries == 0 {
+            continue;
+        }
+        total += retries;
+        let last_error = block.last_error.lock().ok().and_then(|e| e.clone());
+        segments.push(SegmentRetries {
+            index: i,
+            retries,
+            last_error,
+        });
+    }
+    (total, segments)
+}
+
+// ── 多线程分段下载 ────────────────────────────────────────
+
+async fn download_multithread(
+    ctx: &DownloadCtx,
+    client: reqwest::Client,
+    url: String,
+    total_size: u64,
+) -> Result<(), String> {
+    let state_path = &ctx.state_path;
+
+    // 1. 载入上次的续传状态，不匹配则丢弃重来
+    let state = match resume::DownloadState::load(state_path).await {
+        Some(mut state) if state.matches(&ctx.fid, total_size) => {
+            state.reconcile().await;
+            log!(
+                "[download] 发现未完成的下载，从 {:.1}/{:.1} MB 处续传 ({} 块)",
+                state.written() as f64 / 1024.0 / 1024.0,
+                total_size as f64 / 1024.0 / 1024.0,
+                state.segments.len()
+            );
+            state
+        }
+        existing => {
+            if existing.is_some() {
+                log!("[download] 部分数据与当前文件不匹配，重新下载");
+            }
+            let segments = Scheduler::plan(total_size, ctx.thread_count);
+            resume::DownloadState::new(
+                &ctx.fid,
+                &ctx.filename,
+                &ctx.save_path,
+                total_size,
+                segments,
+            )
+        }
+    };
+
+    // 2. 预分配目标文件，各块直接按偏移写入，省掉分片文件与合并阶段
+    let file = open_preallocated(&ctx.save_path, total_size).await?;
+    state.save(state_path).await?;
+
+    // 3. 动态分块调度：worker 数受线程上限与最小块大小约束，空闲 worker 窃取慢块的后半段
+    let scheduler = Arc::new(Scheduler::new(&state.segments));
+    let worker_count = Scheduler::worker_count(total_size, ctx.thread_count);
+    log!(
+        "[download] 启用 {} 个并行连接 ({} 个初始块, 最小块 {} MB)",
+        worker_count,
+        state.segments.len(),
+        scheduler::MIN_BLOCK_SIZE / 1024 / 1024
+    );
+
+    // 4. 进度监控任务（顺带定期保存续传状态）
+    let monitor_progress = Arc::clone(&ctx.progress);
+    let monitor_filename = ctx.filename.clone();
+    let monitor_id = ctx.download_id.clone();
+    let monitor_state_path = state_path.clone();
+    let monitor_state = state.clone();
+    let monitor_scheduler = Arc::clone(&scheduler);
+    let monitor_handle = Arc::clone(&ctx.handle);
+    let monitor = tokio::spawn(async move {
+        let mut last_downloaded: u64 = monitor_state.written();
+        let mut last_time = std::time::Instant::now();
+        let mut last_save = std::time::Instant::now();
+        let mut was_paused = false;
+        loop {
+            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+            if monitor_handle.is_cancelled() {
+                break;
+            }
+            let paused = monitor_handle.is_paused();
+            let downloaded = monitor_scheduler.written();
+            let (retries, retry_segments) = retry_report(&monitor_scheduler);
+            let now = std::time::Instant::now();
+            let dt = now.duration_since(last_time).as_secs_f64();
+            let speed = if dt > 0.0 {
+                (downloaded.saturating_sub(last_downloaded)) as f64 / dt
+            } else {
+                0.0
+            };
+            last_downloaded = downloaded;
+            last_time = now;
+            monitor_handle.update_progress(downloaded, total_size, speed);
+            let status = if paused {
+                ProgressStatus::Paused
+            } else {
+                ProgressStatus::Downloading
+            };
+            monitor_progress.report(DownloadProgress {
+                speed,
+                retries,
+                segments: retry_segments,
+                ..DownloadProgress::new(
+                    &monitor_id,
+                    &monitor_filename,
+                    downloaded,
+                    total_size,
+                    status,
+                )
+            });
+            if downloaded >= total_size {
+                break;
+            }
+            // 每 5 秒落盘一次续传状态；刚进入暂停时立即落盘
+            let just_paused = paused && !was_paused;
+            was_paused = paused;
+            if just_paused || last_save.elapsed() >= std::time::Duration::from_secs(5) {
+                let _ = checkpoint(&monitor_state, &monitor_scheduler)
+                    .save(&monitor_state_path)
+                    .await;
+                last_save = std::time::Instant::now();
+            }
+        }
+    });
+
+    // 5. 启动 worker，各自领取块写入目标文件的对应区间
+    let worker_ctx = Arc::new(WorkerCtx {
+        client,
+        url,
+        cookie: ctx.cookie.clone(),
+        file,
+        scheduler: Arc::clone(&scheduler),
+        handle: Arc::clone(&ctx.handle),
+        max_retries: ctx.max_retries,
+        connections: Arc::clone(&ctx.connections),
+        throttle: Arc::clone(&ctx.throttle),
+    });
+    let mut errors = run_workers(&worker_ctx, worker_count).await;
+
+    // 6. 逐块核对已落盘的字节数：被截断或漏掉的区间只重下这些部分
+    let mut round = 0;
+    while errors.is_empty() && !ctx.handle.is_cancelled() {
+        let gaps = scheduler.missing_ranges(total_size);
+        if gaps.is_empty() {
+            break;
+        }
+        let missing: u64 = gaps.iter().map(|(start, end)| end - start + 1).sum();
+        if round >= REPAIR_ROUNDS {
+            errors.push(format!(
+                "校验后仍有 {} 个区间未下载完整 ({} bytes)",
+                gaps.len(),
+                missing
+            ));
+            break;
+        }
+        round += 1;
+        log!(
+            "[download] 校验发现 {} 个缺失区间 ({} bytes)，第 {}/{} 轮补下",
+            gaps.len(),
+            missing,
+            round,
+            REPAIR_ROUNDS
+        );
+        scheduler.requeue(&gaps);
+        errors = run_workers(&worker_ctx, worker_count.min(gaps.len())).await;
+    }
+
+    monitor.abort();
+
+    let control = ctx.handle.control();
+
+    // 用户取消：删除预分配文件和续传状态
+    if control == DownloadControl::Cancelled {
+        drop(worker_ctx);
+        let _ = tokio::fs::remove_file(&ctx.save_path).await;
+        let _ = tokio::fs::remove_file(state_path).await;
+        log!("[download] 已取消并清理部分数据: {:?}", ctx.save_path);
+        return Err("下载已取消".into());
+    }
+
+    // 失败或中断时保留部分数据，下次下载同一文件时续传
+    if !errors.is_empty() || control == DownloadControl::Stopped {
+        let _ = checkpoint(&state, &scheduler).save(state_path).await;
+        log!("[download] 已保存续传状态: {:?}", state_path);
+        if errors.is_empty() {
+            return Err("下载已中断，部分数据已保留".into());
+        }
+        return Err(format!("多线程下载失败:\n{}", errors.join("\n")));
+    }
+
+    // 7. 落盘并删除续传状态
+    let sync_file = Arc::clone(&worker_ctx.file);
+    tokio::task::spawn_blocking(move || sync_file.sync_all())
+        .await
+        .map_err(|e| format!("flush 任务崩溃: {}", e))?
+        .map_err(|e| format!("flush 失败: {}", e))?;
+    let _ = tokio::fs::remove_file(state_path).await;
+
+    log!(
+        "[download] 多线程完成: {} ({} 块)",
+        ctx.filename,
+        scheduler.blocks().len()
+    );
+    Ok(())
+}
+
+/// 启动一轮 worker 并等待全部结束，返回各 worker 的错误
+async fn run_workers(ctx: &Arc<WorkerCtx>, worker_count: usize) -> Vec<String> {
+    let handles: Vec<_> = (0..worker_count)
+        .map(|_| tokio::spawn(run_worker(Arc::clone(ctx))))
+        .collect();
+    let mut errors = Vec::new();
+    for (i, handle) in handles.into_iter().enumerate() {
+        match handle.await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => errors.push(e),
+            Err(e) => errors.push(format!("worker{} 崩溃: {}", i, e)),
+        }
+    }
+    errors
+}
+
+/// 所有 worker 共享的下载参数
+struct WorkerCtx {
+    client: reqwest::Client,
+    url: String,
+    cookie: String,
+    /// 预分配好的目标文件，所有块共享
+    file: Arc<std::fs::File>,
+    scheduler: Arc<Scheduler>,
+    handle: Arc<DownloadHandle>,
+    max_retries: u32,
+    connections: Arc<ConcurrencyLimit>,
+    throttle: Arc<DownloadThrottle>,
+}
+
+/// 单个 worker：不断向调度器领取块，直到没有可领取或可窃取的块
+async fn run_worker(ctx: Arc<WorkerCtx>) -> Result<(), String> {
+    while let Some((index, block)) = ctx.scheduler.next_block() {
+        let result = download_block(&ctx, index, &block).await;
+        ctx.scheduler.release(&block);
+        if let Err(e) = result {
+            if !ctx.handle.is_cancelled() {
+                ctx.scheduler.abort();
+            }
+            return Err(format!("块{}: {}", index, e));
+        }
+    }
+    Ok(())
+}
+
+/// 单次块请求的失败原因
+enum SegmentError {
+    /// 网络抖动、CDN 5xx 等，可以从已下载位置重试
+    Retryable(String),
+    /// 取消、磁盘写入失败、CDN 不支持 Range 等，重试无意义
+    Fatal(String),
+    /// 下载被暂停：已收到的数据已落盘，恢复后从断点重新请求，不计入重试次数
+    Paused,
+}
+
+/// 单个块的下载逻辑：失败后按指数退避 + 抖动重试，每次都从本块已落盘的位置续传，
+/// 重试次数耗尽才让整个文件失败。每次请求占用一个全局连接名额，暂停和退避期间归还。
+async fn download_block(ctx: &WorkerCtx, index: usize, block: &Block) -> Result<(), String> {
+    let mut attempt: u32 = 0;
+    loop {
+        let permit = ctx.connections.acquire().await;
+        let result = fetch_block(ctx, index, block).await;
+        drop(permit);
+        let err = match result {
+            Ok(()) => return Ok(()),
+            Err(SegmentError::Fatal(e)) => return Err(e),
+            Err(SegmentError::Paused) => {
+                if !ctx.handle.wait_while_paused().await {
+                    return Err("下载已取消".into());
+                }
+                continue;
+            }
+            Err(SegmentError::Retryable(e)) => e,
+        };
+
+        block.record_error(&err);
+        if attempt >= ctx.max_retries {
+            return Err(format!("重试 {} 次后仍失败: {}", attempt, err));
+        }
+        attempt += 1;
+
+        let delay = retry_delay(attempt);
+        log!(
+            "[download] 块{} 第 {}/{} 次重试 ({} ms 后): {}",
+            index,
+            attempt,
+            ctx.max_retries,
+            delay.as_millis(),
+            err
+        );
+        if !sleep_unless_cancelled(delay, &ctx.handle).await {
+            return Err("下载已取消".into());
+        }
+    }
+}
+
+/// 第 n 次重试前的等待时间：指数退避（封顶），再取 [50%, 100%] 区间的随机抖动，
+/// 避免大量块同时失败后又同时重连
+fn retry_delay(attempt: u32) -> std::time::Duration {
+    let exp = RETRY_BASE_DELAY_MS.saturating_mul(1u64 << (attempt - 1).min(16));
+    let capped = exp.min(RETRY_MAX_DELAY_MS);
+    let jittered = rand::thread_rng().gen_range(capped / 2..=capped);
+    std::time::Duration::from_millis(jittered)
+}
+
+/// 等待指定时长，期间下载被取消则提前返回 false
+async fn sleep_unless_cancelled(delay: std::time::Duration, handle: &DownloadHandle) -> bool {
+    let deadline = std::time::Instant::now() + delay;
+    while std::time::Instant::now() < deadline {
+        if handle.is_cancelled() {
+            return false;
+        }
+        let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+        tokio::time::sleep(remaining.min(std::time::Duration::from_millis(200))).await;
+    }
+    !handle.is_cancelled()
+}
+
+/// 单次块请求：从已落盘的位置继续 Range 请求 → 按偏移写入目标文件。
+/// 块的 end 可能在下载途中被其他 worker 窃取而缩小，每收到一段数据都重新读取。
+async fn fetch_block(ctx: &WorkerCtx, index: usize, block: &Block) -> Result<(), SegmentError> {
+    let mut seg_downloaded = block.written();
+    if seg_downloaded >= block.len() {
+        return Ok(());
+    }
+    block.set_received(seg_downloaded);
+
+    let range_header = format!("bytes={}-{}", block.start + seg_downloaded, block.end());
+
+    let resp = add_headers(ctx.client.get(&ctx.url), &ctx.cookie)
+        .header("Range", &range_header)
+        .send()
+        .await
+        .map_err(|e| SegmentError::Retryable(format!("请求失败: {}", e)))?;
+
+    let status = resp.status().as_u16();
+    if status == 200 {
+        return Err(SegmentError::Fatal(
+            "CDN 不支持 Range 请求 (返回 200 而非 206)".into(),
+        ));
+    }
+    if status != 206 {
+        return Err(SegmentError::Retryable(format!(
+            "CDN 返回 {} (期望 206)",
+            status
+        )));
+    }
+
+    let fatal = |msg: &str, e: std::io::Error| SegmentError::Fatal(format!("{}: {}", msg, e));
+    let mut resp = resp;
+    let mut buffer: Vec<u8> = Vec::with_capacity(SEGMENT_BUFFER_SIZE);
+    // 已收到的字节数（含缓冲区中尚未落盘的部分）
+    let mut received = seg_downloaded;
+
+    // 中断/取消时也先把缓冲区写完，保证进度计数与磁盘数据一致
+    let result = loop {
+        if ctx.handle.is_cancelled() {
+            break Err(SegmentError::Fatal("下载已取消".into()));
+        }
+        if ctx.handle.is_paused() {
+            break Err(SegmentError::Paused);
+        }
+        let expected = block.len();
+        if received >= expected {
+            // 后半段已被窃取，或本块已收满：多余的连接直接丢弃
+            break Ok(());
+        }
+        match resp.chunk().await {
+            Ok(Some(chunk)) => {
+                let take = (chunk.len() as u64).min(expected - received) as usize;
+                buffer.extend_from_slice(&chunk[..take]);
+                received += take as u64;
+                block.set_received(received);
+                ctx.throttle.consume(take).await;
+                if buffer.len() >= SEGMENT_BUFFER_SIZE {
+                    let offset = block.start + seg_downloaded;
+                    buffer = match write_at(&ctx.file, buffer, offset).await {
+                        Ok(buf) => buf,
+                        Err(e) => return Err(fatal("写入失败", e)),
+                    };
+                    seg_downloaded = received;
+                    block.set_written(seg_downloaded);
+                }
+            }
+            Ok(None) => {
+                break Err(SegmentError::Retryable(format!(
+                    "连接提前关闭 (已下载 {}/{})",
+                    received, expected
+                )));
+            }
+            Err(e) => {
+                break Err(SegmentError::Retryable(format!(
+                    "传输中断: {} (已下载 {}/{})",
+                    e, received, expected
+                )));
+            }
+        }
+    };
+
+    if !buffer.is_empty() {
+        let offset = block.start + seg_downloaded;
+        write_at(&ctx.file, buffer, offset)
+            .await
+            .map_err(|e| fatal("写入失败", e))?;
+        seg_downloaded = received;
+        block.set_written(seg_downloaded);
+    }
+    result?;
+
+    log!(
+        "[download] 块{} 完成: {}-{} ({} bytes)",
+        index,
+        block.start,
+        block.end(),
+        seg_downloaded
+    );
+
+    Ok(())
+}
+
+// ── 单线程下载（Range 不可用时的回退） ───────────────────
+
+/// 顺序写入响应体，返回写完后文件的总字节数，是否完整由调用方判断
+async fn download_single(
+    ctx: &DownloadCtx,
+    mut resp: reqwest::Response,
+    total_size: u64,
+    offset: u64,
+) -> Result<u64, String> {
+    let DownloadCtx {
+        progress,
+        filename,
+        save_path,
+        handle,
+        download_id,
+        ..
+    } = ctx;
+    // 单线程下载全程只占一条连接
+    let _permit = ctx.connections.acquire().await;
+
+    // offset > 0 时响应体是从已有文件末尾开始的剩余部分，追加写入
+    let file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(offset > 0)
+        .truncate(offset == 0)
+        .open(&save_path)
+        .await
+        .map_err(|e| format!("创建文件失败: {}", e))?;
+    let mut writer = tokio::io::BufWriter::with_capacity(8 * 1024 * 1024, file);
+
+    let mut downloaded: u64 = offset;
+    let mut last_emit = std::time::Instant::now();
+    let mut last_downloaded: u64 = offset;
+
+    loop {
+        // 单线程下载无法用 Range 续传，暂停时只能挂起读取、保持连接
+        if handle.is_paused() {
+            progress.report(DownloadProgress::new(
+                download_id,
+                filename,
+                downloaded,
+                total_size,
+                ProgressStatus::Paused,
+            ));
+            handle.wait_while_paused().await;
+        }
+        if handle.is_cancelled() {
+            // 只有用户取消、且文件完全由本次下载写出时才删除；
+            // 续传时文件前半部分是原有数据，中断（Stopped）时部分数据要留着下次续传
+            if handle.control() == DownloadControl::Cancelled && offset == 0 {
+                drop(writer);
+                let _ = tokio::fs::remove_file(&save_path).await;
+                return Err("下载已取消".into());
+            }
+            writer
+                .flush()
+                .await
+                .map_err(|e| format!("flush 失败: {}", e))?;
+            if handle.control() == DownloadControl::Cancelled {
+                return Err("下载已取消".into());
+            }
+            return Err("下载已中断，部分数据已保留".into());
+        }
+        match resp.chunk().await {
+            Ok(Some(chunk)) => {
+                writer
+                    .write_all(&chunk)
+                    .await
+                    .map_err(|e| format!("写入失败: {}", e))?;
+                downloaded += chunk.len() as u64;
+                ctx.throttle.consume(chunk.len()).await;
+
+                if last_emit.elapsed() >= std::time::Duration::from_millis(500) {
+                    let now = std::time::Instant::now();
+                    let dt = now.duration_since(last_emit).as_secs_f64();
+                    let speed = if dt > 0.0 {
+                        (downloaded.saturating_sub(last_downloaded)) as f64 / dt
+                    } else {
+                        0.0
+                    };
+                    last_downloaded = downloaded;
+                    progress.report(DownloadProgress {
+                        speed,
+                        ..DownloadProgress::new(
+                            download_id,
+                            filename,
+                            downloaded,
+                            total_size,
+                            ProgressStatus::Downloading,
+                        )
+                    });
+                    last_emit = now;
+                }
+            }
+            Ok(None) => break,
+            Err(e) => {
+                // 续传时文件前半部分是原有数据，不能删
+                if offset == 0 {
+                    let _ = tokio::fs::remove_file(&save_path).await;
+                }
+                return Err(format!("下载中断: {} (已下载 {})", e, downloaded));
+            }
+        }
+    }
+
+    writer
+        .flush()
+        .await
+        .map_err(|e| format!("flush 失败: {}", e))?;
+
+    log!(
+        "[download] 单线程完成: {} ({:.1} MB, {} bytes)",
+        filename,
+        downloaded as f64 / 1024.0 / 1024.0,
+        downloaded
+    );
+    Ok(downloaded)
+}
@@ -0,0 +1,41 @@
+//! quark-core：夸克网盘客户端与多线程下载引擎。
+//! 不依赖 Tauri，桌面应用、命令行工具 quark-dl 与无界面服务共用；进度通过 progress::ProgressReporter 上报
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static VERBOSE: AtomicBool = AtomicBool::new(true);
+
+/// 打开或关闭库内的诊断日志。桌面应用保持打开；命令行工具默认关闭，以免打乱进度输出
+pub fn set_verbose(on: bool) {
+    VERBOSE.store(on, Ordering::Relaxed);
+}
+
+pub fn verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+/// 诊断日志，用法同 println!，受 set_verbose 控制
+macro_rules! log {
+    ($($arg:tt)*) => {
+        if $crate::verbose() {
+            println!($($arg)*);
+        }
+    };
+}
+
+pub mod bandwidth;
+pub mod clients;
+pub mod downloader;
+pub mod limiter;
+pub mod progress;
+pub mod proxy;
+pub mod qr;
+pub mod qr_login;
+pub mod quark_client;
+pub mod quark_types;
+pub mod registry;
+pub mod resume;
+pub mod sanitize;
+pub mod scheduler;
+pub mod session;
+pub mod settings;
@@ -0,0 +1,193 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// 可在运行中调整上限的并发闸门。
+/// 放宽时补发许可；收紧时先销毁空闲的许可，不够的部分记为欠账，
+/// 由后台在许可归还时逐个收回，正在使用的许可不受影响。
+pub struct ConcurrencyLimit {
+    semaphore: Arc<Semaphore>,
+    state: Arc<Mutex<LimitState>>,
+}
+
+#[derive(Default)]
+struct LimitState {
+    limit: usize,
+    /// 收紧后还没收回的许可数
+    debt: usize,
+    /// 后台收回任务是否在运行
+    collecting: bool,
+}
+
+impl ConcurrencyLimit {
+    pub fn new(limit: usize) -> Self {
+        let limit = limit.max(1);
+        Self {
+            semaphore: Arc::new(Semaphore::new(limit)),
+            state: Arc::new(Mutex::new(LimitState {
+                limit,
+                ..Default::default()
+            })),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.state.lock().map(|s| s.limit).unwrap_or(1)
+    }
+
+    /// 等待一个许可，drop 时自动归还
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        Arc::clone(&self.semaphore)
+            .acquire_owned()
+            .await
+            .expect("semaphore closed")
+    }
+
+    /// 调整上限。欠账与增减都在同一把锁下结算，放宽时先抵消尚未收回的欠账，
+    /// 这样无论调整多少次、后台任务进行到哪一步，实际许可总数都与上限一致
+    pub fn set_limit(&self, limit: usize) {
+        let limit = limit.max(1);
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        if limit > state.limit {
+            let grow = limit - state.limit;
+            let cancelled = grow.min(state.debt);
+            state.debt -= cancelled;
+            self.semaphore.add_permits(grow - cancelled);
+        } else if limit < state.limit {
+            let shrink = state.limit - limit;
+            let forgotten = self.semaphore.forget_permits(shrink);
+            state.debt += shrink - forgotten;
+            if state.debt > 0 && !state.collecting {
+                state.collecting = true;
+                tokio::spawn(collect_debt(
+                    Arc::clone(&self.semaphore),
+                    Arc::clone(&self.state),
+                ));
+            }
+        }
+        state.limit = limit;
+    }
+}
+
+/// 逐个等待归还的许可并销毁，直到欠账还清；欠账被放宽抵消后拿到的许可原样归还
+async fn collect_debt(semaphore: Arc<Semaphore>, state: Arc<Mutex<LimitState>>) {
+    loop {
+        let Ok(permit) = Arc::clone(&semaphore).acquire_owned().await else {
+            return;
+        };
+        let Ok(mut guard) = state.lock() else {
+            return;
+        };
+        if guard.debt > 0 {
+            guard.debt -= 1;
+            permit.forget();
+        }
+        if guard.debt == 0 {
+            guard.collecting = false;
+            return;
+        }
+    }
+}
+
+/// 令牌桶：按 rate 个/秒补充令牌，最多攒 1 秒的量，新建时是满的。
+/// 取用时允许透支，透支部分换算成调用方需要等待的时间，多个调用方共享时自然按比例分摊。
+/// 速率由调用方每次传入，便于运行中调整。
+pub struct TokenBucket {
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new((f64::MAX, Instant::now())),
+        }
+    }
+
+    /// 预扣 amount 个令牌，返回需要等待的时长；rate 不大于 0 表示不限速
+    pub fn reserve(&self, amount: f64, rate: f64) -> Duration {
+        let Ok(mut state) = self.state.lock() else {
+            return Duration::ZERO;
+        };
+        let (tokens, last) = &mut *state;
+        let now = Instant::now();
+        if rate <= 0.0 {
+            *tokens = 0.0;
+            *last = now;
+            return Duration::ZERO;
+        }
+        *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * rate).min(rate);
+        *last = now;
+        *tokens -= amount;
+        if *tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-*tokens / rate)
+        }
+    }
+
+    /// 速率变化后清掉按旧速率累积的透支
+    pub fn reset(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            *state = (0.0, Instant::now());
+        }
+    }
+}
+
+impl Default for TokenBucket {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 让后台收回任务跑完
+    async fn settle() {
+        for _ in 0..20 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn shrinking_takes_idle_permits_immediately() {
+        let limit = ConcurrencyLimit::new(4);
+        limit.set_limit(2);
+        assert_eq!(limit.semaphore.available_permits(), 2);
+        assert_eq!(limit.limit(), 2);
+    }
+
+    #[tokio::test]
+    async fn busy_permits_are_collected_when_returned() {
+        let limit = ConcurrencyLimit::new(4);
+        let held: Vec<_> = [(); 3]
+            .iter()
+            .map(|_| limit.semaphore.clone().try_acquire_owned().unwrap())
+            .collect();
+        limit.set_limit(1);
+        assert_eq!(limit.semaphore.available_permits(), 0);
+        drop(held);
+        settle().await;
+        assert_eq!(limit.semaphore.available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn raising_before_collection_cancels_the_debt() {
+        let limit = ConcurrencyLimit::new(4);
+        let held: Vec<_> = [(); 4]
+            .iter()
+            .map(|_| limit.semaphore.clone().try_acquire_owned().unwrap())
+            .collect();
+        limit.set_limit(1);
+        limit.set_limit(6);
+        assert_eq!(limit.semaphore.available_permits(), 2);
+        drop(held);
+        settle().await;
+        // 收回任务不能吞掉放宽时补发的许可
+        assert_eq!(limit.semaphore.available_permits(), 6);
+        assert_eq!(limit.limit(), 6);
+    }
+}
@@ -0,0 +1,79 @@
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+/// 下载所处阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgressStatus {
+    Downloading,
+    Paused,
+    Verifying,
+    Done,
+}
+
+/// 发生过重试的块
+#[derive(Debug, Clone, Serialize)]
+pub struct SegmentRetries {
+    pub index: usize,
+    pub retries: u32,
+    pub last_error: Option<String>,
+}
+
+/// 一次进度上报。序列化结果即桌面应用 download-progress 事件的负载
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadProgress {
+    pub id: String,
+    pub filename: String,
+    pub downloaded: u64,
+    pub total: u64,
+    /// 字节/秒
+    pub speed: f64,
+    pub status: ProgressStatus,
+    /// 累计重试次数（仅多线程下载）
+    pub retries: u32,
+    pub segments: Vec<SegmentRetries>,
+}
+
+impl DownloadProgress {
+    pub fn new(
+        id: &str,
+        filename: &str,
+        downloaded: u64,
+        total: u64,
+        status: ProgressStatus,
+    ) -> Self {
+        Self {
+            id: id.to_string(),
+            filename: filename.to_string(),
+            downloaded,
+            total,
+            speed: 0.0,
+            status,
+            retries: 0,
+            segments: Vec::new(),
+        }
+    }
+}
+
+/// 下载进度的接收方。引擎只通过它上报进度，不关心进度最终去向：
+/// 桌面应用转发为窗口事件，命令行工具画到终端，测试里收进通道。
+/// 在下载任务内同步调用，实现不应阻塞
+pub trait ProgressReporter: Send + Sync {
+    fn report(&self, progress: DownloadProgress);
+}
+
+impl<F> ProgressReporter for F
+where
+    F: Fn(DownloadProgress) + Send + Sync,
+{
+    fn report(&self, progress: DownloadProgress) {
+        self(progress)
+    }
+}
+
+/// 接收端已关闭时丢弃进度，不影响下载
+impl ProgressReporter for mpsc::UnboundedSender<DownloadProgress> {
+    fn report(&self, progress: DownloadProgress) {
+        let _ = self.send(progress);
+    }
+}
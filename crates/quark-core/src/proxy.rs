@@ -0,0 +1,96 @@
+use crate::settings::{CdnProxyMode, ProxyConfig, ProxySettings};
+use url::Url;
+
+/// 流量类别：夸克 API（含扫码登录）与 CDN 下载可以走不同的代理
+#[derive(Debug, Clone, Copy)]
+pub enum ProxyTarget {
+    Api,
+    Cdn,
+}
+
+enum Route {
+    /// 未配置，沿用 reqwest 默认行为（读取系统代理环境变量）
+    System,
+    Direct,
+    Proxy(ProxyConfig),
+}
+
+/// 打印各类流量实际使用的代理
+pub fn log_routes(settings: &ProxySettings) {
+    for target in [ProxyTarget::Api, ProxyTarget::Cdn] {
+        match route(settings, target) {
+            Route::System => log!("[proxy] {:?}: 使用系统代理设置", target),
+            Route::Direct => log!("[proxy] {:?}: 直连", target),
+            Route::Proxy(config) => log!("[proxy] {:?}: {}", target, redact(&config.url)),
+        }
+    }
+}
+
+/// 把代理设置应用到客户端构建器上
+pub fn apply(
+    builder: reqwest::ClientBuilder,
+    settings: &ProxySettings,
+    target: ProxyTarget,
+) -> reqwest::ClientBuilder {
+    match route(settings, target) {
+        Route::System => builder,
+        Route::Direct => builder.no_proxy(),
+        Route::Proxy(config) => match build(&config) {
+            Ok(proxy) => builder.proxy(proxy),
+            Err(e) => {
+                log!("[proxy] 代理配置无效，{:?} 流量不使用代理: {}", target, e);
+                builder
+            }
+        },
+    }
+}
+
+/// 由配置构造 reqwest 代理；用户名密码写进 URL，HTTP 与 SOCKS5 代理都能认证
+pub fn build(config: &ProxyConfig) -> Result<reqwest::Proxy, String> {
+    let mut url = Url::parse(config.url.trim())
+        .map_err(|e| format!("代理地址无效（应形如 http://host:port）: {}", e))?;
+    if !matches!(url.scheme(), "http" | "https" | "socks5" | "socks5h") {
+        return Err(format!("不支持的代理协议: {}", url.scheme()));
+    }
+    if url.host_str().is_none() {
+        return Err(format!("代理地址缺少主机名: {}", config.url));
+    }
+    if let Some(username) = config.username.as_deref().filter(|u| !u.is_empty()) {
+        url.set_username(username)
+            .map_err(|_| "代理用户名无效".to_string())?;
+        url.set_password(config.password.as_deref())
+            .map_err(|_| "代理密码无效".to_string())?;
+    }
+    let proxy = reqwest::Proxy::all(url.as_str()).map_err(|e| format!("代理地址无效: {}", e))?;
+    let no_proxy = config
+        .no_proxy
+        .as_deref()
+        .and_then(reqwest::NoProxy::from_string);
+    Ok(proxy.no_proxy(no_proxy))
+}
+
+fn route(settings: &ProxySettings, target: ProxyTarget) -> Route {
+    let api = || match &settings.api {
+        Some(config) => Route::Proxy(config.clone()),
+        None => Route::System,
+    };
+    match (target, settings.cdn_mode) {
+        (ProxyTarget::Api, _) | (ProxyTarget::Cdn, CdnProxyMode::SameAsApi) => api(),
+        (ProxyTarget::Cdn, CdnProxyMode::Direct) => Route::Direct,
+        (ProxyTarget::Cdn, CdnProxyMode::Custom) => match &settings.cdn {
+            Some(config) => Route::Proxy(config.clone()),
+            None => Route::Direct,
+        },
+    }
+}
+
+/// 日志里隐去代理 URL 中可能带的密码
+fn redact(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut parsed) if parsed.password().is_some() => {
+            let _ = parsed.set_password(Some("***"));
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
+}
@@ -0,0 +1,91 @@
+use qrcode::{Color, EcLevel};
+
+/// 终端扫码登录用的二维码：由 qrcode crate 编码，纠错等级 L（与前端 qrcode.react 的默认一致），
+/// 这里只负责画成终端文本
+pub struct QrCode {
+    size: usize,
+    modules: Vec<bool>,
+}
+
+impl QrCode {
+    /// 编码为能容纳数据的最小版本
+    pub fn encode(data: &[u8]) -> Result<Self, String> {
+        let code = qrcode::QrCode::with_error_correction_level(data, EcLevel::L)
+            .map_err(|e| format!("无法生成二维码（{} 字节）: {}", data.len(), e))?;
+        Ok(Self {
+            size: code.width(),
+            modules: code
+                .into_colors()
+                .into_iter()
+                .map(|c| c == Color::Dark)
+                .collect(),
+        })
+    }
+
+    /// 边长（模块数）
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// (x, y) 处是否为深色模块，超出范围视为浅色
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        x < self.size && y < self.size && self.modules[y * self.size + x]
+    }
+
+    /// 用半高方块字符画成文本，两行模块占一行字符，四周留标准要求的 4 个模块静区。
+    /// 默认按深色背景终端绘制（浅色模块画成方块）；浅色背景终端传 invert = true
+    pub fn render_terminal(&self, invert: bool) -> String {
+        const QUIET: usize = 4;
+        let span = self.size + QUIET * 2;
+        let light = |x: usize, y: usize| {
+            let dark = x >= QUIET && y >= QUIET && self.is_dark(x - QUIET, y - QUIET);
+            dark == invert
+        };
+        let mut out = String::with_capacity(span * span.div_ceil(2) * 3);
+        for y in (0..span).step_by(2) {
+            for x in 0..span {
+                // 最后一行落单时，下半格按静区处理
+                let bottom = if y + 1 < span {
+                    light(x, y + 1)
+                } else {
+                    !invert
+                };
+                out.push(match (light(x, y), bottom) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_smallest_version() {
+        assert_eq!(QrCode::encode(&[b'a'; 17]).unwrap().size(), 21);
+        assert_eq!(QrCode::encode(&[b'a'; 18]).unwrap().size(), 25);
+        // 登录链接的长度
+        assert_eq!(QrCode::encode(&[b'a'; 200]).unwrap().size(), 9 * 4 + 17);
+        assert!(QrCode::encode(&[b'a'; 3000]).is_err());
+    }
+
+    #[test]
+    fn terminal_rendering_has_quiet_zone() {
+        let qr = QrCode::encode(b"hello").unwrap();
+        let text = qr.render_terminal(false);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), (21usize + 8).div_ceil(2));
+        assert!(lines.iter().all(|l| l.chars().count() == 29));
+        assert!(lines[..2].iter().all(|l| l.chars().all(|c| c == '█')));
+        // 第 3 行字符对应定位图形的前两行：外圈整列深色，内侧上深下浅
+        assert_eq!(lines[2].chars().nth(4), Some(' '));
+        assert_eq!(lines[2].chars().nth(5), Some('▄'));
+    }
+}
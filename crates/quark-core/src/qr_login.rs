@@ -0,0 +1,190 @@
+use crate::clients::ApiClient;
+use crate::qr::QrCode;
+use crate::quark_client::{self, QuarkError};
+use crate::session::CookieJar;
+use serde_json::Value;
+use std::time::{Duration, Instant};
+
+/// 二维码有效期，与前端扫码弹窗一致
+pub const QR_LIFETIME: Duration = Duration::from_secs(120);
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+const STATUS_OK: i64 = 2000000;
+/// 二维码已失效
+const STATUS_EXPIRED: i64 = 50004002;
+/// 已扫码，等待在手机上确认
+const STATUS_SCANNED: i64 = 50004003;
+
+/// 扫码页地址，与前端 AuthModal 生成的二维码内容相同
+const SCAN_PAGE: &str = "https://su.quark.cn/4_eMHBJ";
+const SCAN_PAGE_BIZ: &str = "S:custom|OPT:SAREA@0|OPT:IMMERSIVE@1|OPT:BACK_BTN_STYLE@0";
+
+/// 扫码登录的状态，只会按 Waiting → Scanned → Confirmed 前进，或在任意时刻转为 Expired
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QrLoginState {
+    Waiting,
+    Scanned,
+    Confirmed,
+    Expired,
+}
+
+impl QrLoginState {
+    pub fn describe(self) -> &'static str {
+        match self {
+            QrLoginState::Waiting => "等待扫码",
+            QrLoginState::Scanned => "已扫码，请在手机上确认登录",
+            QrLoginState::Confirmed => "已确认，正在获取 Cookie",
+            QrLoginState::Expired => "二维码已过期",
+        }
+    }
+}
+
+/// 不经过前端的扫码登录：申请二维码 → 轮询扫码状态 → 用 service_ticket 换 Cookie。
+/// 命令行工具与无界面运行时使用，二维码直接画在终端里
+pub struct QrLogin {
+    url: String,
+    token: String,
+    deadline: Instant,
+}
+
+impl QrLogin {
+    /// 申请登录二维码
+    pub async fn start(client: &ApiClient) -> Result<Self, QuarkError> {
+        let data = quark_client::qr_get_token(client).await?;
+        let status = data["status"].as_i64().unwrap_or_default();
+        let token = data["data"]["members"]["token"]
+            .as_str()
+            .filter(|t| status == STATUS_OK && !t.is_empty())
+            .ok_or_else(|| QuarkError::Api {
+                code: status,
+                message: data["message"]
+                    .as_str()
+                    .unwrap_or("获取登录二维码失败")
+                    .to_string(),
+            })?;
+        Ok(Self {
+            url: scan_url(token),
+            token: token.to_string(),
+            deadline: Instant::now() + QR_LIFETIME,
+        })
+    }
+
+    /// 二维码内容
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// 画成终端文本，参数见 QrCode::render_terminal
+    pub fn render_terminal(&self, invert: bool) -> Result<String, String> {
+        QrCode::encode(self.url.as_bytes()).map(|qr| qr.render_terminal(invert))
+    }
+
+    /// 轮询直到确认登录或二维码过期，状态每变化一次调用一次 on_state（开始时先报 Waiting）。
+    /// 确认后换取 Cookie 返回；过期返回 Timeout。单次轮询失败只记日志，继续等待
+    pub async fn wait(
+        &self,
+        client: &ApiClient,
+        mut on_state: impl FnMut(QrLoginState),
+    ) -> Result<CookieJar, QuarkError> {
+        let mut state = QrLoginState::Waiting;
+        on_state(state);
+        loop {
+            let (next, ticket) = if Instant::now() >= self.deadline {
+                (QrLoginState::Expired, None)
+            } else {
+                match quark_client::qr_query_status(client, &self.token).await {
+                    Ok(result) => {
+                        let body: Value =
+                            serde_json::from_slice(&result.body).unwrap_or(Value::Null);
+                        classify(&body)
+                    }
+                    Err(e) => {
+                        log!("[qrlogin] 查询扫码状态失败: {}", e);
+                        (state, None)
+                    }
+                }
+            };
+            // 服务端在扫码前后都可能返回“等待”，不让状态倒退
+            if next != state && !(state == QrLoginState::Scanned && next == QrLoginState::Waiting) {
+                state = next;
+                on_state(state);
+            }
+            match (state, ticket) {
+                (QrLoginState::Confirmed, Some(st)) => return exchange(client, &st).await,
+                (QrLoginState::Expired, _) => {
+                    return Err(QuarkError::Timeout("二维码已过期，请重新获取".into()));
+                }
+                _ => tokio::time::sleep(POLL_INTERVAL).await,
+            }
+        }
+    }
+}
+
+fn scan_url(token: &str) -> String {
+    format!(
+        "{}?token={}&client_id=532&ssb=weblogin&uc_param_str=&uc_biz_str={}",
+        SCAN_PAGE,
+        urlencoding::encode(token),
+        urlencoding::encode(SCAN_PAGE_BIZ)
+    )
+}
+
+/// 解析一次轮询的响应：确认登录时带上 service_ticket，未识别的状态码按等待处理
+fn classify(body: &Value) -> (QrLoginState, Option<String>) {
+    let ticket = body["data"]["members"]["service_ticket"]
+        .as_str()
+        .filter(|t| !t.is_empty());
+    match (body["status"].as_i64(), ticket) {
+        (Some(STATUS_OK), Some(st)) => (QrLoginState::Confirmed, Some(st.to_string())),
+        (Some(STATUS_EXPIRED), _) => (QrLoginState::Expired, None),
+        (Some(STATUS_SCANNED), _) => (QrLoginState::Scanned, None),
+        _ => (QrLoginState::Waiting, None),
+    }
+}
+
+async fn exchange(client: &ApiClient, st: &str) -> Result<CookieJar, QuarkError> {
+    let data = quark_client::qr_get_cookie(client, st).await?;
+    let jar = CookieJar::parse(data["cookie"].as_str().unwrap_or_default());
+    if jar.is_empty() {
+        return Err(QuarkError::Decode("登录未返回 Cookie".into()));
+    }
+    if data["missing_puus"].as_bool().unwrap_or(false) {
+        log!("[qrlogin] Cookie 缺少 __puus，下载功能可能受限");
+    }
+    Ok(jar)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn classify_poll_responses() {
+        let waiting = json!({ "status": 50004001, "message": "waiting" });
+        assert_eq!(classify(&waiting), (QrLoginState::Waiting, None));
+        let scanned = json!({ "status": STATUS_SCANNED });
+        assert_eq!(classify(&scanned), (QrLoginState::Scanned, None));
+        let expired = json!({ "status": STATUS_EXPIRED });
+        assert_eq!(classify(&expired), (QrLoginState::Expired, None));
+        let confirmed =
+            json!({ "status": STATUS_OK, "data": { "members": { "service_ticket": "st-1" } } });
+        assert_eq!(
+            classify(&confirmed),
+            (QrLoginState::Confirmed, Some("st-1".to_string()))
+        );
+        // 成功状态但缺少 ticket 不能算确认
+        let incomplete = json!({ "status": STATUS_OK, "data": { "members": {} } });
+        assert_eq!(classify(&incomplete), (QrLoginState::Waiting, None));
+        assert_eq!(classify(&Value::Null), (QrLoginState::Waiting, None));
+    }
+
+    #[test]
+    fn scan_url_matches_frontend() {
+        assert_eq!(
+            scan_url("a b"),
+            "https://su.quark.cn/4_eMHBJ?token=a%20b&client_id=532&ssb=weblogin&uc_param_str=\
+             &uc_biz_str=S%3Acustom%7COPT%3ASAREA%400%7COPT%3AIMMERSIVE%401%7COPT%3ABACK_BTN_STYLE%400"
+        );
+    }
+}
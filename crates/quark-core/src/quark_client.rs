@@ -0,0 +1,1517 @@
+use crate::clients::{self, ApiClient};
+use crate::quark_types::{
+    AccountInfoResponse, ApiEnvelope, DeleteRequest, DownloadItem, DownloadRequest, ListMetadata,
+    MemberData, MemberQuery, SaveData, SaveRequest, ShareDetailData, ShareDetailQuery, ShareItem,
+    ShareTokenData, ShareTokenRequest, TaskData, TaskQuery,
+};
+use reqwest::Method;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use url::Url;
+
+const HOST_PAN: &str = "pan.quark.cn";
+const HOST_DRIVE_PC: &str = "drive-pc.quark.cn";
+const HOST_DRIVE: &str = "drive.quark.cn";
+const UOP_HOST: &str = "uop.quark.cn";
+const COMMON_PARAMS: &str = "pr=ucpro&fr=pc&uc_param_str=";
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) quark-cloud-drive/2.5.20 Chrome/100.0.4896.160 Electron/18.3.5.4-b478491100 Safari/537.36 Channel/pckk_other_ch";
+const LOGOUT_CALLBACK_URL: &str = "https://pan.quark.cn";
+
+/// 夸克业务码
+const CODE_RATE_LIMITED: i64 = 23018;
+const CODE_NOT_LOGGED_IN: i64 = 31001;
+const CODE_CAPACITY_EXCEEDED: i64 = 32003;
+
+/// API 路由映射
+pub struct ApiRoute {
+    pub path: &'static str,
+    pub method: Method,
+    pub host: &'static str,
+}
+
+pub fn get_api_routes() -> HashMap<&'static str, ApiRoute> {
+    let mut m = HashMap::new();
+    m.insert(
+        "/api/share/token",
+        ApiRoute {
+            path: "/1/clouddrive/share/sharepage/token",
+            method: Method::POST,
+            host: HOST_PAN,
+        },
+    );
+    m.insert(
+        "/api/share/detail",
+        ApiRoute {
+            path: "/1/clouddrive/share/sharepage/detail",
+            method: Method::GET,
+            host: HOST_PAN,
+        },
+    );
+    m.insert(
+        "/api/share/save",
+        ApiRoute {
+            path: "/1/clouddrive/share/sharepage/save",
+            method: Method::POST,
+            host: HOST_DRIVE_PC,
+        },
+    );
+    m.insert(
+        "/api/task",
+        ApiRoute {
+            path: "/1/clouddrive/task",
+            method: Method::GET,
+            host: HOST_DRIVE_PC,
+        },
+    );
+    m.insert(
+        "/api/file/download",
+        ApiRoute {
+            path: "/1/clouddrive/file/download",
+            method: Method::POST,
+            host: HOST_DRIVE,
+        },
+    );
+    m.insert(
+        "/api/file/delete",
+        ApiRoute {
+            path: "/1/clouddrive/file/delete",
+            method: Method::POST,
+            host: HOST_DRIVE,
+        },
+    );
+    m.insert(
+        "/api/member",
+        ApiRoute {
+            path: "/1/clouddrive/member",
+            method: Method::GET,
+            host: HOST_DRIVE,
+        },
+    );
+    m
+}
+
+// ==================== 错误类型 ====================
+
+/// 夸克接口调用失败的原因
+#[derive(Debug, Clone)]
+pub enum QuarkError {
+    /// 请求没能完成（DNS、连接、读取响应体失败等）
+    Network(String),
+    /// 上游返回非 2xx，且无法归入下面的具体类别
+    Http { status: u16, message: String },
+    /// Cookie 未登录或已失效
+    AuthExpired(String),
+    /// 请求过于频繁或被限速（如 23018）
+    RateLimited { code: i64, message: String },
+    /// 网盘容量不足，转存失败
+    CapacityExceeded(String),
+    /// 未识别的业务错误码
+    Api { code: i64, message: String },
+    /// 响应格式与预期不符
+    Decode(String),
+    /// 调用参数不合法，如无法解析的分享链接
+    InvalidInput(String),
+    /// 轮询等待超时，如转存任务迟迟未完成
+    Timeout(String),
+}
+
+impl QuarkError {
+    /// 根据 HTTP 状态、业务码与提示文案归类错误
+    fn classify(action: &str, http_status: u16, code: i64, message: &str) -> Self {
+        let message = if message.is_empty() {
+            format!("{}失败", action)
+        } else {
+            format!("{}失败: {}", action, message)
+        };
+
+        if code == CODE_RATE_LIMITED || http_status == 429 || message.contains("频繁") {
+            return QuarkError::RateLimited { code, message };
+        }
+        if code == CODE_NOT_LOGGED_IN
+            || http_status == 401
+            || message.contains("未登录")
+            || message.contains("登录失效")
+            || message.to_lowercase().contains("require login")
+        {
+            return QuarkError::AuthExpired(message);
+        }
+        if code == CODE_CAPACITY_EXCEEDED || message.contains("容量不足") {
+            return QuarkError::CapacityExceeded(message);
+        }
+        if code != 0 {
+            return QuarkError::Api { code, message };
+        }
+        QuarkError::Http {
+            status: http_status,
+            message,
+        }
+    }
+
+    /// 机器可读的类别名，随 JSON 错误体返回
+    pub fn kind(&self) -> &'static str {
+        match self {
+            QuarkError::Network(_) => "network",
+            QuarkError::Http { .. } => "http",
+            QuarkError::AuthExpired(_) => "auth_expired",
+            QuarkError::RateLimited { .. } => "rate_limited",
+            QuarkError::CapacityExceeded(_) => "capacity_exceeded",
+            QuarkError::Api { .. } => "api",
+            QuarkError::Decode(_) => "decode",
+            QuarkError::InvalidInput(_) => "invalid_input",
+            QuarkError::Timeout(_) => "timeout",
+        }
+    }
+
+    /// 夸克业务码（有的话）
+    pub fn code(&self) -> Option<i64> {
+        match self {
+            QuarkError::RateLimited { code, .. } | QuarkError::Api { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for QuarkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuarkError::Network(m) => write!(f, "网络请求失败: {}", m),
+            QuarkError::Http { status, message } => write!(f, "{} (HTTP {})", message, status),
+            QuarkError::AuthExpired(m) => write!(f, "{}（登录已失效，请重新登录）", m),
+            QuarkError::RateLimited { code, message } => {
+                write!(f, "{}（请求被限流 {}）", message, code)
+            }
+            QuarkError::CapacityExceeded(m) => write!(f, "{}（网盘容量不足）", m),
+            QuarkError::Api { code, message } => write!(f, "{} (code {})", message, code),
+            QuarkError::Decode(m) => write!(f, "响应解析失败: {}", m),
+            QuarkError::InvalidInput(m) | QuarkError::Timeout(m) => write!(f, "{}", m),
+        }
+    }
+}
+
+impl std::error::Error for QuarkError {}
+
+/// Tauri 命令与下载流程仍以 String 作为错误类型
+impl From<QuarkError> for String {
+    fn from(e: QuarkError) -> Self {
+        e.to_string()
+    }
+}
+
+pub struct ProxyResponse {
+    pub status: u16,
+    pub headers: reqwest::header::HeaderMap,
+    pub body: bytes::Bytes,
+}
+
+/// 发送请求并读完响应体，按主机限速，失败时按策略重试：
+/// - 连接失败（请求尚未到达服务器）任何方法都重试
+/// - 超时、读响应体失败、5xx 只对 GET / HEAD 这类幂等请求重试，避免重复转存、重复删除
+/// - HTTP 429 或夸克限流业务码任何方法都重试，优先遵循 Retry-After
+///
+/// 重试次数用完后，最后一次的响应照常返回，由调用方归类错误
+async fn send_with_retry(
+    client: &ApiClient,
+    host: &str,
+    method: &Method,
+    build: impl Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+) -> Result<ProxyResponse, QuarkError> {
+    let idempotent = matches!(*method, Method::GET | Method::HEAD);
+    let mut attempt = 0u32;
+    loop {
+        client.wait_turn(host).await;
+        let result = match build(client.http()).send().await {
+            Ok(resp) => {
+                let status = resp.status().as_u16();
+                let headers = resp.headers().clone();
+                resp.bytes().await.map(|body| ProxyResponse {
+                    status,
+                    headers,
+                    body,
+                })
+            }
+            Err(e) => Err(e),
+        };
+        let can_retry = attempt < client.max_retries();
+        attempt += 1;
+
+        let resp = match result {
+            Ok(resp) => resp,
+            Err(e) => {
+                if can_retry && (e.is_connect() || idempotent) {
+                    let delay = clients::retry_delay(attempt);
+                    log!(
+                        "[api] {} {} 失败，{}ms 后第 {} 次重试: {}",
+                        method,
+                        host,
+                        delay.as_millis(),
+                        attempt,
+                        e
+                    );
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+                if e.is_timeout() {
+                    return Err(QuarkError::Timeout(format!("请求 {} 超时", host)));
+                }
+                return Err(QuarkError::Network(e.to_string()));
+            }
+        };
+
+        let throttled = resp.status == 429 || is_rate_limited(&resp.body);
+        if !can_retry || !(throttled || (idempotent && resp.status >= 500)) {
+            return Ok(resp);
+        }
+        let delay = match clients::retry_after(&resp.headers) {
+            // 要求等待太久，不如直接把限流错误交给调用方
+            Some(delay) if delay > clients::MAX_RETRY_AFTER => return Ok(resp),
+            Some(delay) => delay,
+            None => clients::retry_delay(attempt),
+        };
+        log!(
+            "[api] {} {} 返回 {}{}，{}ms 后第 {} 次重试",
+            method,
+            host,
+            resp.status,
+            if throttled { "（限流）" } else { "" },
+            delay.as_millis(),
+            attempt
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// 响应体是否带有夸克的限流业务码
+fn is_rate_limited(body: &[u8]) -> bool {
+    serde_json::from_slice::<ApiEnvelope<serde::de::IgnoredAny>>(body)
+        .is_ok_and(|head| head.code == Some(CODE_RATE_LIMITED))
+}
+
+/// 通用代理请求 — 对应 JS 版 proxyRequest()
+#[allow(clippy::too_many_arguments)]
+pub async fn proxy_request(
+    client: &ApiClient,
+    target_path: &str,
+    method: Method,
+    cookie: Option<&str>,
+    body: Option<bytes::Bytes>,
+    query: &str,
+    hostname: &str,
+    append_common: bool,
+) -> Result<ProxyResponse, QuarkError> {
+    let mut full_path = target_path.to_string();
+    if append_common {
+        let sep = if full_path.contains('?') { '&' } else { '?' };
+        full_path = format!("{}{}{}", full_path, sep, COMMON_PARAMS);
+    }
+    if !query.is_empty() {
+        let sep = if full_path.contains('?') { '&' } else { '?' };
+        full_path = format!("{}{}{}", full_path, sep, query);
+    }
+
+    let url = format!("https://{}{}", hostname, full_path);
+
+    send_with_retry(client, hostname, &method, |http| {
+        let mut req = http
+            .request(method.clone(), &url)
+            .header("User-Agent", USER_AGENT)
+            .header("Referer", "https://pan.quark.cn/")
+            .header("Origin", "https://pan.quark.cn")
+            .header("Accept", "application/json, text/plain, */*")
+            .header("Accept-Language", "zh-CN,zh;q=0.9")
+            .header("Content-Type", "application/json;charset=UTF-8");
+
+        if let Some(ck) = cookie {
+            if !ck.is_empty() {
+                req = req.header("Cookie", ck);
+            }
+        }
+
+        if let Some(b) = &body {
+            req = req.body(b.clone());
+        }
+        req
+    })
+    .await
+}
+
+/// 调用夸克 JSON 接口：检查 HTTP 状态与业务码，失败时归类为 QuarkError，成功时解析为强类型响应
+#[allow(clippy::too_many_arguments)]
+async fn call_api<T: DeserializeOwned>(
+    client: &ApiClient,
+    action: &str,
+    method: Method,
+    host: &str,
+    path: &str,
+    cookie: &str,
+    query: Option<&impl Serialize>,
+    body: Option<&impl Serialize>,
+) -> Result<ApiEnvelope<T>, QuarkError> {
+    let query = query
+        .map(serde_urlencoded::to_string)
+        .transpose()
+        .map_err(|e| QuarkError::InvalidInput(format!("{}参数无效: {}", action, e)))?
+        .unwrap_or_default();
+    let body = body
+        .map(serde_json::to_vec)
+        .transpose()
+        .map_err(|e| QuarkError::InvalidInput(format!("{}参数无效: {}", action, e)))?
+        .map(bytes::Bytes::from);
+
+    let result =
+        proxy_request(client, path, method, Some(cookie), body, &query, host, true).await?;
+
+    // 先只看状态字段，失败响应的 data 结构往往与成功时不同
+    let head: ApiEnvelope<serde::de::IgnoredAny> = match serde_json::from_slice(&result.body) {
+        Ok(head) => head,
+        Err(_) if !(200..300).contains(&result.status) => {
+            return Err(QuarkError::classify(action, result.status, 0, ""));
+        }
+        Err(e) => return Err(QuarkError::Decode(format!("{}: {}", action, e))),
+    };
+    let ok = (200..300).contains(&result.status)
+        && head.status.is_none_or(|s| s == 200)
+        && head.code.is_none_or(|c| c == 0);
+    if !ok {
+        return Err(QuarkError::classify(
+            action,
+            result.status,
+            head.code.unwrap_or(0),
+            &head.message,
+        ));
+    }
+
+    serde_json::from_slice(&result.body)
+        .map_err(|e| QuarkError::Decode(format!("{}: {}", action, e)))
+}
+
+/// 取出响应中的 data，缺失时视为格式错误
+fn require_data<T>(envelope: ApiEnvelope<T>, action: &str) -> Result<T, QuarkError> {
+    envelope
+        .data
+        .ok_or_else(|| QuarkError::Decode(format!("{}: 响应中缺少 data", action)))
+}
+
+pub struct CookieResponse {
+    pub body: bytes::Bytes,
+    pub cookies: Vec<String>,
+}
+
+/// 带重定向跟踪的请求 — 对应 JS 版 fetchWithCookies()
+pub async fn fetch_with_cookies(
+    client: &ApiClient,
+    start_url: &str,
+    existing_cookies: Vec<String>,
+) -> Result<CookieResponse, QuarkError> {
+    let mut all_cookies = existing_cookies;
+    let mut current_url = start_url.to_string();
+    let mut redirects = 0u32;
+
+    loop {
+        if redirects > 5 {
+            return Err(QuarkError::Network("Too many redirects".into()));
+        }
+
+        let cookie_header: String = all_cookies
+            .iter()
+            .filter_map(|c| c.split(';').next())
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        let host = Url::parse(&current_url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string))
+            .unwrap_or_default();
+        let resp = send_with_retry(client, &host, &Method::GET, |http| {
+            http.get(&current_url)
+                .header("User-Agent", USER_AGENT)
+                .header("Referer", "https://pan.quark.cn/")
+                .header("Accept", "text/html,application/json,*/*")
+                .header("Cookie", &cookie_header)
+        })
+        .await?;
+
+        // 收集 set-cookie
+        for val in resp.headers.get_all("set-cookie") {
+            if let Ok(s) = val.to_str() {
+                all_cookies.push(s.to_string());
+            }
+        }
+
+        if (300..400).contains(&resp.status) {
+            if let Some(loc) = resp.headers.get("location") {
+                let loc_str = loc
+                    .to_str()
+                    .map_err(|e| QuarkError::Decode(e.to_string()))?;
+                current_url = Url::parse(&current_url)
+                    .and_then(|base| base.join(loc_str))
+                    .map(|u| u.to_string())
+                    .unwrap_or_else(|_| loc_str.to_string());
+                redirects += 1;
+                continue;
+            }
+        }
+
+        return Ok(CookieResponse {
+            body: resp.body,
+            cookies: all_cookies,
+        });
+    }
+}
+
+/// 拼接 cookie — 对应 JS 版 buildCookieHeader()
+pub fn build_cookie_header(raw_cookies: &[String]) -> String {
+    let mut latest: HashMap<String, String> = HashMap::new();
+    for raw in raw_cookies {
+        let pair = raw.split(';').next().unwrap_or("").trim();
+        if pair.is_empty() {
+            continue;
+        }
+        if let Some(eq) = pair.find('=') {
+            let name = pair[..eq].trim().to_string();
+            let value = pair[eq + 1..].to_string();
+            if !name.is_empty() {
+                latest.insert(name, value);
+            }
+        }
+    }
+    latest
+        .into_iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// 拆分 cookie 字符串 — 对应 JS 版 splitCookieHeader()
+pub fn split_cookie_header(header: &str) -> Vec<String> {
+    header
+        .split(';')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+pub struct AccountVerifyResult {
+    pub status_code: u16,
+    pub info: AccountInfoResponse,
+}
+
+/// 用 cookie 检查账号状态 — 对应 JS 版 fetchAccountInfoByCookie()
+pub async fn fetch_account_info_by_cookie(
+    client: &ApiClient,
+    cookie_header: &str,
+) -> Result<AccountVerifyResult, QuarkError> {
+    let result = proxy_request(
+        client,
+        "/account/info",
+        Method::GET,
+        Some(cookie_header),
+        None,
+        "fr=pc&platform=pc",
+        HOST_PAN,
+        false,
+    )
+    .await?;
+
+    // 未登录时可能返回 HTML 或空体，按空响应处理
+    let info: AccountInfoResponse = serde_json::from_slice(&result.body).unwrap_or_default();
+    Ok(AccountVerifyResult {
+        status_code: result.status,
+        info,
+    })
+}
+
+/// 能否从 /account/info 读到有效信息
+pub fn can_read_account_info(status: u16, info: &AccountInfoResponse) -> bool {
+    (200..300).contains(&status) && info.is_success() && info.has_data()
+}
+
+/// 查询网盘容量等会员信息
+pub async fn get_member(client: &ApiClient, cookie: &str) -> Result<MemberData, QuarkError> {
+    let query = MemberQuery {
+        fetch_subscribe: true,
+        channel: "home",
+        fetch_identity: true,
+    };
+    let envelope = call_api(
+        client,
+        "获取会员信息",
+        Method::GET,
+        HOST_DRIVE,
+        "/1/clouddrive/member",
+        cookie,
+        Some(&query),
+        None::<&()>,
+    )
+    .await?;
+    require_data(envelope, "获取会员信息")
+}
+
+// ==================== 扫码登录 ====================
+
+pub async fn qr_get_token(client: &ApiClient) -> Result<Value, QuarkError> {
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let path = format!(
+        "/cas/ajax/getTokenForQrcodeLogin?client_id=532&v=1.2&request_id={}",
+        request_id
+    );
+    let result = proxy_request(client, &path, Method::GET, None, None, "", UOP_HOST, false).await?;
+    let mut data: Value = serde_json::from_slice(&result.body).unwrap_or(Value::Null);
+    data.as_object_mut()
+        .map(|o| o.insert("_request_id".into(), Value::String(request_id)));
+    Ok(data)
+}
+
+pub async fn qr_query_status(client: &ApiClient, token: &str) -> Result<ProxyResponse, QuarkError> {
+    let poll_id = uuid::Uuid::new_v4().to_string();
+    let path = format!(
+        "/cas/ajax/getServiceTicketByQrcodeToken?client_id=532&v=1.2&token={}&request_id={}",
+        urlencoding::encode(token),
+        urlencoding::encode(&poll_id)
+    );
+    proxy_request(client, &path, Method::GET, None, None, "", UOP_HOST, false).await
+}
+
+/// 用 service_ticket 换 cookie（含多步补全 __puus）
+pub async fn qr_get_cookie(client: &ApiClient, st: &str) -> Result<Value, QuarkError> {
+    let url = format!(
+        "https://pan.quark.cn/account/info?st={}&lw=scan",
+        urlencoding::encode(st)
+    );
+
+    let result = fetch_with_cookies(client, &url, vec![]).await?;
+    let mut all_raw = result.cookies;
+    let mut cookie_str = all_raw
+        .iter()
+        .filter_map(|c| c.split(';').next())
+        .map(|s| s.trim().to_string())
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    log!(
+        "[qrlogin/cookie] Step1 Cookie 字段: {}",
+        extract_cookie_keys(&cookie_str)
+    );
+
+    // Step 2-4: 尝试补全 __puus
+    let puus_steps = [
+        ("Step2", "https://pan.quark.cn/list"),
+        ("Step3", "https://drive-pc.quark.cn/1/clouddrive/file/sort?pr=ucpro&fr=pc&uc_param_str=&pdir_fid=0&_page=1&_size=50&_fetch_total=1&_sort=file_type:asc,updated_at:desc"),
+        ("Step4", "https://drive.quark.cn/1/clouddrive/member?pr=ucpro&fr=pc&uc_param_str=&fetch_subscribe=true"),
+    ];
+
+    for (step, step_url) in puus_steps {
+        if cookie_str.contains("__puus=") {
+            break;
+        }
+        log!("[qrlogin/cookie] 缺少 __puus, 尝试 {} ...", step);
+        match fetch_with_cookies(client, step_url, all_raw.clone()).await {
+            Ok(r) => {
+                if r.cookies.len() > all_raw.len() {
+                    all_raw = r.cookies;
+                    cookie_str = all_raw
+                        .iter()
+                        .filter_map(|c| c.split(';').next())
+                        .map(|s| s.trim().to_string())
+                        .collect::<Vec<_>>()
+                        .join("; ");
+                    log!(
+                        "[qrlogin/cookie] {} 新增 Cookie, 总字段: {}",
+                        step,
+                        extract_cookie_keys(&cookie_str)
+                    );
+                } else {
+                    log!("[qrlogin/cookie] {} 未获取到新 Cookie", step);
+                }
+            }
+            Err(e) => log!("[qrlogin/cookie] {} 失败: {}", step, e),
+        }
+    }
+
+    let has_puus = cookie_str.contains("__puus=");
+    if !has_puus {
+        log!("[qrlogin/cookie] 警告: 最终仍缺少 __puus, 下载功能可能受限");
+    }
+
+    let user_info: Value = serde_json::from_slice(&result.body).unwrap_or(Value::Null);
+
+    Ok(serde_json::json!({
+        "cookie": cookie_str,
+        "user_info": user_info,
+        "missing_puus": !has_puus
+    }))
+}
+
+// ==================== 退出登录 ====================
+
+pub async fn logout(client: &ApiClient, raw_cookie: &str) -> Result<Value, QuarkError> {
+    if raw_cookie.trim().is_empty() {
+        return Err(QuarkError::InvalidInput(
+            "缺少登录 Cookie，请先登录后再退出".into(),
+        ));
+    }
+
+    let logout_url = format!(
+        "https://pan.quark.cn/account/logout?callback={}",
+        urlencoding::encode(LOGOUT_CALLBACK_URL)
+    );
+
+    let mut logout_request_error = String::new();
+    let mut verify_cookie = raw_cookie.to_string();
+
+    match fetch_with_cookies(client, &logout_url, split_cookie_header(raw_cookie)).await {
+        Ok(flow) => {
+            let merged = build_cookie_header(&flow.cookies);
+            if !merged.is_empty() {
+                verify_cookie = merged;
+            }
+        }
+        Err(e) => {
+            logout_request_error = e.to_string();
+        }
+    }
+
+    let mut verify_error = String::new();
+    let account = match fetch_account_info_by_cookie(client, &verify_cookie).await {
+        Ok(info) => info,
+        Err(e) => {
+            verify_error = e.to_string();
+            AccountVerifyResult {
+                status_code: 0,
+                info: AccountInfoResponse::default(),
+            }
+        }
+    };
+    let nickname = account.info.nickname();
+
+    let can_read = can_read_account_info(account.status_code, &account.info);
+    let logged_out = if verify_error.is_empty() {
+        !can_read
+    } else {
+        false
+    };
+
+    let message = if !logout_request_error.is_empty() && logged_out {
+        "退出请求返回异常，但账号状态已失效".to_string()
+    } else if !logout_request_error.is_empty() && !logged_out {
+        "退出请求失败，夸克账号仍处于登录状态".to_string()
+    } else if !logged_out && !nickname.is_empty() {
+        format!("退出未生效，当前仍为账号「{}」", nickname)
+    } else if logged_out {
+        "夸克账号已退出登录".to_string()
+    } else {
+        "夸克账号仍处于登录状态，请稍后重试".to_string()
+    };
+
+    Ok(serde_json::json!({
+        "ok": logout_request_error.is_empty() && verify_error.is_empty(),
+        "logged_out": logged_out,
+        "message": message,
+        "logout_request_error": logout_request_error,
+        "verify_error": verify_error,
+        "verify": {
+            "endpoint": "/account/info?fr=pc&platform=pc",
+            "status_code": account.status_code,
+            "code": account.info.code,
+            "message": account.info.message,
+            "nickname": nickname,
+        }
+    }))
+}
+
+fn extract_cookie_keys(cookie_str: &str) -> String {
+    cookie_str
+        .split(';')
+        .filter_map(|s| s.trim().split('=').next())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// ==================== 转存 / 下载链接 ====================
+
+/// 单次 /file/download 请求携带的 fid 上限
+const DOWNLOAD_LINK_BATCH: usize = 20;
+/// 等待转存任务：每 500ms 查询一次，最多 20 次
+const SAVE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+const SAVE_POLL_ATTEMPTS: u32 = 20;
+/// 等待失败后在后台继续查询的节奏：每 5 秒一次，最多 5 分钟
+const LATE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+const LATE_POLL_ATTEMPTS: u32 = 60;
+
+/// 转存到自己网盘的临时文件。
+/// 应在用完后调用 cleanup()；若未清理就被丢弃（出错提前返回、任务被取消），Drop 时在后台补删，
+/// 保证网盘里不留下转存残留
+pub struct SavedFiles {
+    client: ApiClient,
+    cookie: String,
+    /// 提交转存的分享 fid
+    share_fids: Vec<String>,
+    fids: Vec<String>,
+    cleaned: bool,
+}
+
+impl SavedFiles {
+    /// 转存后网盘中的文件 fid，与转存时提交的顺序一致
+    pub fn fids(&self) -> &[String] {
+        &self.fids
+    }
+
+    /// (分享 fid, 转存后的 fid) 一一对应；数量与提交时不一致的转存在 save_to_drive 中已报错
+    pub fn pairs(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.share_fids
+            .iter()
+            .zip(&self.fids)
+            .map(|(share, saved)| (share.as_str(), saved.as_str()))
+    }
+
+    /// 删除转存产生的文件
+    pub async fn cleanup(mut self) -> Result<(), QuarkError> {
+        self.cleaned = true;
+        delete_files(&self.client, &self.cookie, &self.fids).await
+    }
+}
+
+impl Drop for SavedFiles {
+    fn drop(&mut self) {
+        if self.cleaned || self.fids.is_empty() {
+            return;
+        }
+        let client = self.client.clone();
+        let cookie = std::mem::take(&mut self.cookie);
+        let fids = std::mem::take(&mut self.fids);
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(async move {
+                    match delete_files(&client, &cookie, &fids).await {
+                        Ok(()) => log!("[transfer] 已在后台清理 {} 个转存文件", fids.len()),
+                        Err(e) => log!("[transfer] 后台清理转存文件失败: {}", e),
+                    }
+                });
+            }
+            Err(_) => log!("[transfer] 无法清理转存文件（运行时已关闭）: {:?}", fids),
+        }
+    }
+}
+
+/// 已提交但还没拿到结果的转存任务。
+/// 等待超时、完成后没返回 fid 或等待途中被取消时，转存可能已经生效；
+/// Drop 时在后台按任务 ID 继续查询，拿到 fid 后删除，避免网盘里留下转存残留
+struct PendingSave {
+    client: ApiClient,
+    cookie: String,
+    task_id: String,
+    settled: bool,
+}
+
+impl Drop for PendingSave {
+    fn drop(&mut self) {
+        if self.settled {
+            return;
+        }
+        let client = self.client.clone();
+        let cookie = std::mem::take(&mut self.cookie);
+        let task_id = std::mem::take(&mut self.task_id);
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(async move {
+                    let late =
+                        poll_save_task(LATE_POLL_ATTEMPTS, LATE_POLL_INTERVAL, |retry_index| {
+                            query_save_task(&client, &cookie, &task_id, retry_index)
+                        })
+                        .await;
+                    let result = match late {
+                        Ok(fids) => delete_files(&client, &cookie, &fids)
+                            .await
+                            .map(|_| fids.len()),
+                        Err(e) => Err(e),
+                    };
+                    match result {
+                        Ok(count) => log!("[transfer] 已在后台清理 {} 个迟到的转存文件", count),
+                        Err(e) => log!(
+                            "[transfer] 未能确认转存任务 {} 的结果，网盘中可能留有转存文件: {}",
+                            task_id,
+                            e
+                        ),
+                    }
+                });
+            }
+            Err(_) => log!("[transfer] 无法清理转存任务 {}（运行时已关闭）", task_id),
+        }
+    }
+}
+
+/// 转存并等待任务完成，返回代表这批临时文件的 SavedFiles
+pub async fn save_to_drive(
+    client: &ApiClient,
+    cookie: &str,
+    pwd_id: &str,
+    stoken: &str,
+    fids: &[String],
+    fid_tokens: &[String],
+) -> Result<SavedFiles, QuarkError> {
+    let task_id = save_share_files(client, cookie, pwd_id, stoken, fids, fid_tokens).await?;
+    let mut pending = PendingSave {
+        client: client.clone(),
+        cookie: cookie.to_string(),
+        task_id,
+        settled: false,
+    };
+    let saved_fids = wait_save_task(client, cookie, &pending.task_id).await?;
+    pending.settled = true;
+    let saved = SavedFiles {
+        client: client.clone(),
+        cookie: cookie.to_string(),
+        share_fids: fids.to_vec(),
+        fids: saved_fids,
+        cleaned: false,
+    };
+    // 接口只按顺序返回转存后的 fid，数量对不上时无法确定对应关系；直接返回错误，saved 被丢弃时清理
+    if saved.fids.len() != fids.len() {
+        return Err(QuarkError::Decode(format!(
+            "转存结果与提交的文件对不上（提交 {} 个，返回 {} 个）",
+            fids.len(),
+            saved.fids.len()
+        )));
+    }
+    Ok(saved)
+}
+
+/// 把分享中的文件转存到自己网盘根目录，返回转存任务 ID
+pub async fn save_share_files(
+    client: &ApiClient,
+    cookie: &str,
+    pwd_id: &str,
+    stoken: &str,
+    fids: &[String],
+    fid_tokens: &[String],
+) -> Result<String, QuarkError> {
+    let request = SaveRequest {
+        fid_list: fids,
+        fid_token_list: fid_tokens,
+        to_pdir_fid: "0",
+        pwd_id,
+        stoken,
+        pdir_fid: "0",
+        scene: "link",
+    };
+    let envelope = call_api::<SaveData>(
+        client,
+        "转存",
+        Method::POST,
+        HOST_DRIVE_PC,
+        "/1/clouddrive/share/sharepage/save",
+        cookie,
+        None::<&()>,
+        Some(&request),
+    )
+    .await?;
+    Ok(require_data(envelope, "转存")?.task_id)
+}
+
+/// 轮询转存任务直到完成，返回转存后网盘中的文件 fid
+pub async fn wait_save_task(
+    client: &ApiClient,
+    cookie: &str,
+    task_id: &str,
+) -> Result<Vec<String>, QuarkError> {
+    poll_save_task(SAVE_POLL_ATTEMPTS, SAVE_POLL_INTERVAL, |retry_index| {
+        query_save_task(client, cookie, task_id, retry_index)
+    })
+    .await
+}
+
+async fn query_save_task(
+    client: &ApiClient,
+    cookie: &str,
+    task_id: &str,
+    retry_index: u32,
+) -> Result<TaskData, QuarkError> {
+    let query = TaskQuery {
+        task_id,
+        retry_index,
+    };
+    let envelope = call_api::<TaskData>(
+        client,
+        "查询转存任务",
+        Method::GET,
+        HOST_DRIVE_PC,
+        "/1/clouddrive/task",
+        cookie,
+        Some(&query),
+        None::<&()>,
+    )
+    .await?;
+    require_data(envelope, "查询转存任务")
+}
+
+/// 每隔 interval 查询一次，最多 attempts 次；任务完成但还没有 fid 时继续等
+async fn poll_save_task<F, Fut>(
+    attempts: u32,
+    interval: std::time::Duration,
+    mut query: F,
+) -> Result<Vec<String>, QuarkError>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: std::future::Future<Output = Result<TaskData, QuarkError>>,
+{
+    let mut finished = false;
+    for retry_index in 0..attempts {
+        tokio::time::sleep(interval).await;
+        let task = query(retry_index).await?;
+        if task.status == TaskData::FINISHED {
+            let fids = task.save_as.map(|s| s.save_as_top_fids).unwrap_or_default();
+            if !fids.is_empty() {
+                return Ok(fids);
+            }
+            finished = true;
+        }
+    }
+    if finished {
+        Err(QuarkError::Decode("转存完成但未获取到文件 ID".into()))
+    } else {
+        Err(QuarkError::Timeout("转存超时".into()))
+    }
+}
+
+/// 批量获取网盘文件的 CDN 下载链接（链接自带鉴权，删除网盘文件后仍然有效），
+/// 每批最多 DOWNLOAD_LINK_BATCH 个；没有返回链接的文件（如目录）不在结果中
+pub async fn get_download_links(
+    client: &ApiClient,
+    cookie: &str,
+    fids: &[String],
+) -> Result<Vec<DownloadItem>, QuarkError> {
+    let mut items = Vec::with_capacity(fids.len());
+    for batch in fids.chunks(DOWNLOAD_LINK_BATCH) {
+        let envelope = call_api::<Vec<DownloadItem>>(
+            client,
+            "获取下载链接",
+            Method::POST,
+            HOST_DRIVE,
+            "/1/clouddrive/file/download",
+            cookie,
+            None::<&()>,
+            Some(&DownloadRequest { fids: batch }),
+        )
+        .await?;
+        items.extend(
+            require_data(envelope, "获取下载链接")?
+                .into_iter()
+                .filter(|item| !item.download_url.is_empty()),
+        );
+    }
+    Ok(items)
+}
+
+/// 获取单个网盘文件的 CDN 下载链接（连同文件名、大小与 MD5）
+pub async fn get_download_link(
+    client: &ApiClient,
+    cookie: &str,
+    fid: &str,
+) -> Result<DownloadItem, QuarkError> {
+    get_download_links(client, cookie, std::slice::from_ref(&fid.to_string()))
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| QuarkError::Decode("获取下载链接: 响应中缺少 download_url".into()))
+}
+
+/// 删除网盘中的文件（清理转存产生的临时文件）
+pub async fn delete_files(
+    client: &ApiClient,
+    cookie: &str,
+    fids: &[String],
+) -> Result<(), QuarkError> {
+    let request = DeleteRequest {
+        action_type: 2,
+        filelist: fids,
+        exclude_fids: &[],
+    };
+    call_api::<serde::de::IgnoredAny>(
+        client,
+        "删除文件",
+        Method::POST,
+        HOST_DRIVE,
+        "/1/clouddrive/file/delete",
+        cookie,
+        None::<&()>,
+        Some(&request),
+    )
+    .await
+    .map(|_| ())
+}
+
+// ==================== 分享解析 ====================
+
+const SHARE_PAGE_SIZE: u64 = 50;
+/// 解析分享目录时同时在途的请求数
+const SHARE_RESOLVE_CONCURRENCY: usize = 8;
+
+/// 从分享链接中提取的信息
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareLink {
+    pub pwd_id: String,
+    pub passcode: String,
+    /// 链接指向的子目录，根目录为 "0"
+    pub pdir_fid: String,
+}
+
+/// 分享中的一个文件或目录
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareNode {
+    pub fid: String,
+    pub file_name: String,
+    pub size: u64,
+    pub format_type: String,
+    pub updated_at: u64,
+    pub share_fid_token: String,
+    pub is_dir: bool,
+    pub depth: usize,
+    /// 相对分享根目录的路径
+    pub path: String,
+    pub children: Vec<ShareNode>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareTree {
+    #[serde(flatten)]
+    pub link: ShareLink,
+    /// 分享标题，可用作本地保存时的顶层目录名
+    pub title: String,
+    pub stoken: String,
+    pub files: Vec<ShareNode>,
+    pub file_count: usize,
+    pub dir_count: usize,
+}
+
+/// 取字符串开头连续的字母数字
+fn take_alnum(s: &str) -> &str {
+    let end = s
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(s.len());
+    &s[..end]
+}
+
+/// 解析分享链接 — 对应前端 parseShareUrl()：
+/// /s/<pwd_id>、?pwd=xxxx（也认 passcode/password/pw）、#/list/share/<fid>
+pub fn parse_share_url(raw: &str) -> Result<ShareLink, QuarkError> {
+    // 去掉分享文案里的 [标题] 之类的方括号片段
+    let mut clean = String::new();
+    let mut depth = 0usize;
+    for c in raw.chars() {
+        match c {
+            '[' => depth += 1,
+            ']' if depth > 0 => depth -= 1,
+            _ if depth == 0 => clean.push(c),
+            _ => {}
+        }
+    }
+    let clean = clean.trim();
+
+    let pwd_id = clean
+        .find("/s/")
+        .map(|i| take_alnum(&clean[i + 3..]))
+        .unwrap_or("")
+        .to_string();
+    if pwd_id.is_empty() {
+        return Err(QuarkError::InvalidInput("无法从链接中提取分享 ID".into()));
+    }
+
+    let passcode = clean
+        .split(['?', '&'])
+        .skip(1)
+        .filter_map(|part| part.split_once('='))
+        .find(|(key, value)| {
+            matches!(
+                key.to_lowercase().as_str(),
+                "pwd" | "passcode" | "password" | "pw"
+            ) && take_alnum(value).len() >= 4
+        })
+        .map(|(_, value)| value[..4].to_string())
+        .unwrap_or_default();
+
+    let pdir_fid = clean
+        .find("#/list/share/")
+        .map(|i| take_alnum(&clean[i + "#/list/share/".len()..]))
+        .filter(|s| !s.is_empty())
+        .unwrap_or("0")
+        .to_string();
+
+    Ok(ShareLink {
+        pwd_id,
+        passcode,
+        pdir_fid,
+    })
+}
+
+/// 用提取码换取分享访问令牌 stoken（响应中同时带有分享标题）
+pub async fn get_share_token(
+    client: &ApiClient,
+    cookie: &str,
+    pwd_id: &str,
+    passcode: &str,
+) -> Result<ShareTokenData, QuarkError> {
+    let envelope = call_api::<ShareTokenData>(
+        client,
+        "获取访问令牌",
+        Method::POST,
+        HOST_PAN,
+        "/1/clouddrive/share/sharepage/token",
+        cookie,
+        None::<&()>,
+        Some(&ShareTokenRequest { pwd_id, passcode }),
+    )
+    .await?;
+    require_data(envelope, "获取访问令牌")
+}
+
+/// 拉取某目录的一页，返回 (条目, 分页信息)
+async fn fetch_share_page(
+    client: &ApiClient,
+    cookie: &str,
+    pwd_id: &str,
+    stoken: &str,
+    pdir_fid: &str,
+    page: u64,
+) -> Result<(Vec<ShareItem>, ListMetadata), QuarkError> {
+    let query = ShareDetailQuery {
+        pwd_id,
+        stoken,
+        pdir_fid,
+        force: 0,
+        page,
+        size: SHARE_PAGE_SIZE,
+        fetch_total: 1,
+        fetch_sub_dirs: 0,
+        sort: "file_type:asc,file_name:asc",
+    };
+    let envelope = call_api::<ShareDetailData>(
+        client,
+        "获取文件列表",
+        Method::GET,
+        HOST_PAN,
+        "/1/clouddrive/share/sharepage/detail",
+        cookie,
+        Some(&query),
+        None::<&()>,
+    )
+    .await?;
+    let list = envelope.data.map(|d| d.list).unwrap_or_default();
+    Ok((list, envelope.metadata))
+}
+
+/// 解析整个分享：换取 stoken 后并发拉取所有目录的所有分页（同时在途的请求数有上限），
+/// 最后按目录关系组装成树
+pub async fn resolve_share(
+    client: &ApiClient,
+    cookie: &str,
+    url: &str,
+) -> Result<ShareTree, QuarkError> {
+    let link = parse_share_url(url)?;
+    let ShareTokenData { stoken, title } =
+        get_share_token(client, cookie, &link.pwd_id, &link.passcode).await?;
+    log!(
+        "[share] 开始解析分享 {} (目录 {})",
+        link.pwd_id,
+        link.pdir_fid
+    );
+    let started = std::time::Instant::now();
+
+    let pool = std::sync::Arc::new(tokio::sync::Semaphore::new(SHARE_RESOLVE_CONCURRENCY));
+    let mut tasks = tokio::task::JoinSet::new();
+    let spawn_page = |tasks: &mut tokio::task::JoinSet<_>, pdir_fid: String, page: u64| {
+        let pool = std::sync::Arc::clone(&pool);
+        let client = client.clone();
+        let (cookie, pwd_id, stoken) = (cookie.to_string(), link.pwd_id.clone(), stoken.clone());
+        tasks.spawn(async move {
+            let _permit = pool
+                .acquire_owned()
+                .await
+                .map_err(|e| QuarkError::Network(e.to_string()))?;
+            let (list, metadata) =
+                fetch_share_page(&client, &cookie, &pwd_id, &stoken, &pdir_fid, page).await?;
+            Ok::<_, QuarkError>((pdir_fid, page, list, metadata.total))
+        });
+    };
+
+    // 目录 fid → [(页码, 条目)]
+    let mut pages: HashMap<String, Vec<(u64, Vec<ShareItem>)>> = HashMap::new();
+    spawn_page(&mut tasks, link.pdir_fid.clone(), 1);
+
+    while let Some(joined) = tasks.join_next().await {
+        let (pdir_fid, page, list, total) =
+            joined.map_err(|e| QuarkError::Network(format!("解析任务崩溃: {}", e)))??;
+
+        if page == 1 {
+            // 第一页拿到总数后一次性派发其余分页；没有总数时按满页逐页往后翻
+            match total {
+                Some(total) => {
+                    let last = total.div_ceil(SHARE_PAGE_SIZE);
+                    for p in 2..=last {
+                        spawn_page(&mut tasks, pdir_fid.clone(), p);
+                    }
+                }
+                None if list.len() as u64 >= SHARE_PAGE_SIZE => {
+                    spawn_page(&mut tasks, pdir_fid.clone(), 2);
+                }
+                None => {}
+            }
+        } else if total.is_none() && list.len() as u64 >= SHARE_PAGE_SIZE {
+            spawn_page(&mut tasks, pdir_fid.clone(), page + 1);
+        }
+
+        for item in list.iter().filter(|item| item.is_dir()) {
+            spawn_page(&mut tasks, item.fid.clone(), 1);
+        }
+        pages.entry(pdir_fid).or_default().push((page, list));
+    }
+
+    let files = build_share_nodes(&mut pages, &link.pdir_fid, 0, "");
+    let (mut file_count, mut dir_count) = (0, 0);
+    count_share_nodes(&files, &mut file_count, &mut dir_count);
+    log!(
+        "[share] 解析完成: {} 个文件, {} 个目录, 耗时 {:.1}s",
+        file_count,
+        dir_count,
+        started.elapsed().as_secs_f64()
+    );
+
+    Ok(ShareTree {
+        link,
+        title,
+        stoken,
+        files,
+        file_count,
+        dir_count,
+    })
+}
+
+fn build_share_nodes(
+    pages: &mut HashMap<String, Vec<(u64, Vec<ShareItem>)>>,
+    pdir_fid: &str,
+    depth: usize,
+    parent_path: &str,
+) -> Vec<ShareNode> {
+    let Some(mut dir_pages) = pages.remove(pdir_fid) else {
+        return Vec::new();
+    };
+    dir_pages.sort_by_key(|(page, _)| *page);
+
+    let mut nodes = Vec::new();
+    for item in dir_pages.into_iter().flat_map(|(_, list)| list) {
+        let path = if parent_path.is_empty() {
+            item.file_name.clone()
+        } else {
+            format!("{}/{}", parent_path, item.file_name)
+        };
+        let is_dir = item.is_dir();
+        let children = if is_dir {
+            build_share_nodes(pages, &item.fid, depth + 1, &path)
+        } else {
+            Vec::new()
+        };
+        nodes.push(ShareNode {
+            updated_at: item.updated_at.or(item.l_updated_at).unwrap_or(0),
+            fid: item.fid,
+            file_name: item.file_name,
+            size: item.size,
+            format_type: item.format_type,
+            share_fid_token: item.share_fid_token,
+            is_dir,
+            depth,
+            path,
+            children,
+        });
+    }
+    nodes
+}
+
+fn count_share_nodes(nodes: &[ShareNode], files: &mut usize, dirs: &mut usize) {
+    for node in nodes {
+        if node.is_dir {
+            *dirs += 1;
+            count_share_nodes(&node.children, files, dirs);
+        } else {
+            *files += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quark_types::SaveAs;
+    use std::time::Duration;
+
+    /// 第 finish_at 次查询起才完成的转存任务
+    fn task(
+        finish_at: u32,
+        fids: &[&str],
+    ) -> impl FnMut(u32) -> std::future::Ready<Result<TaskData, QuarkError>> {
+        let fids: Vec<String> = fids.iter().map(|f| f.to_string()).collect();
+        let mut calls = 0;
+        move |_| {
+            calls += 1;
+            let finished = calls >= finish_at;
+            std::future::ready(Ok(TaskData {
+                status: if finished { TaskData::FINISHED } else { 1 },
+                save_as: finished.then(|| SaveAs {
+                    save_as_top_fids: fids.clone(),
+                }),
+            }))
+        }
+    }
+
+    #[tokio::test]
+    async fn late_poll_recovers_fids_after_the_wait_times_out() {
+        let mut query = task(25, &["saved-1", "saved-2"]);
+        let waited = poll_save_task(SAVE_POLL_ATTEMPTS, Duration::ZERO, &mut query).await;
+        assert!(matches!(waited, Err(QuarkError::Timeout(_))));
+        // 超时之后同一个任务继续查，拿到的 fid 交给清理
+        let late = poll_save_task(LATE_POLL_ATTEMPTS, Duration::ZERO, &mut query).await;
+        assert_eq!(late.unwrap(), ["saved-1", "saved-2"]);
+    }
+
+    #[test]
+    fn classifies_codes_and_statuses() {
+        let cases: &[(u16, i64, &str, &str)] = &[
+            (200, CODE_RATE_LIMITED, "", "rate_limited"),
+            (429, 0, "", "rate_limited"),
+            (200, 1, "请求过于频繁", "rate_limited"),
+            (401, 0, "", "auth_expired"),
+            (200, CODE_NOT_LOGGED_IN, "", "auth_expired"),
+            (200, 1, "require login [guest]", "auth_expired"),
+            (200, CODE_CAPACITY_EXCEEDED, "", "capacity_exceeded"),
+            (200, 1, "网盘容量不足", "capacity_exceeded"),
+            (200, 41004, "文件不存在", "api"),
+            (500, 0, "", "http"),
+        ];
+        for &(status, code, message, kind) in cases {
+            let e = QuarkError::classify("转存", status, code, message);
+            assert_eq!(
+                e.kind(),
+                kind,
+                "HTTP {} code {} {:?}",
+                status,
+                code,
+                message
+            );
+        }
+        // 限流与未识别的业务码都带上原始业务码
+        assert_eq!(
+            QuarkError::classify("转存", 200, CODE_RATE_LIMITED, "").code(),
+            Some(CODE_RATE_LIMITED)
+        );
+        assert_eq!(
+            QuarkError::classify("转存", 200, 41004, "").code(),
+            Some(41004)
+        );
+        assert_eq!(QuarkError::classify("转存", 500, 0, "").code(), None);
+    }
+
+    #[test]
+    fn parses_share_urls() {
+        let cases = [
+            ("https://pan.quark.cn/s/abc123", ("abc123", "", "0")),
+            ("https://pan.quark.cn/s/abc123/", ("abc123", "", "0")),
+            (
+                "https://pan.quark.cn/s/abc123?pwd=Xy9z",
+                ("abc123", "Xy9z", "0"),
+            ),
+            (
+                "https://pan.quark.cn/s/abc123?entry=sharer&passcode=ab12cd&from=x",
+                ("abc123", "ab12", "0"),
+            ),
+            (
+                "https://pan.quark.cn/s/abc123?pwd=ab12#/list/share/9f8e7d",
+                ("abc123", "ab12", "9f8e7d"),
+            ),
+            (
+                "[我的分享] 链接：https://pan.quark.cn/s/abc123#/list/share/9f8e7d/",
+                ("abc123", "", "9f8e7d"),
+            ),
+        ];
+        for (raw, (pwd_id, passcode, pdir_fid)) in cases {
+            let link = parse_share_url(raw).unwrap();
+            assert_eq!(
+                (
+                    link.pwd_id.as_str(),
+                    link.passcode.as_str(),
+                    link.pdir_fid.as_str()
+                ),
+                (pwd_id, passcode, pdir_fid),
+                "{}",
+                raw
+            );
+        }
+        for raw in [
+            "",
+            "https://pan.quark.cn/list#/list/all",
+            "https://pan.quark.cn/s/",
+        ] {
+            assert!(
+                matches!(parse_share_url(raw), Err(QuarkError::InvalidInput(_))),
+                "{}",
+                raw
+            );
+        }
+    }
+
+    fn item(fid: &str, name: &str, dir: bool) -> ShareItem {
+        serde_json::from_value(serde_json::json!({
+            "fid": fid,
+            "file_name": name,
+            "size": if dir { 0 } else { 10 },
+            "dir": dir,
+            "share_fid_token": format!("token-{}", fid),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn builds_tree_from_paged_listing() {
+        let mut pages = HashMap::new();
+        // 页码乱序到达，按页码拼回原顺序
+        pages.insert(
+            "0".to_string(),
+            vec![
+                (2, vec![item("f2", "b.txt", false)]),
+                (
+                    1,
+                    vec![item("d1", "season1", true), item("f1", "a.txt", false)],
+                ),
+            ],
+        );
+        pages.insert(
+            "d1".to_string(),
+            vec![(1, vec![item("f3", "e01.mkv", false)])],
+        );
+
+        let nodes = build_share_nodes(&mut pages, "0", 0, "");
+        let names: Vec<&str> = nodes.iter().map(|n| n.file_name.as_str()).collect();
+        assert_eq!(names, ["season1", "a.txt", "b.txt"]);
+        assert!(nodes[0].is_dir);
+        let child = &nodes[0].children[0];
+        assert_eq!(
+            (
+                child.path.as_str(),
+                child.depth,
+                child.share_fid_token.as_str()
+            ),
+            ("season1/e01.mkv", 1, "token-f3")
+        );
+        let (mut files, mut dirs) = (0, 0);
+        count_share_nodes(&nodes, &mut files, &mut dirs);
+        assert_eq!((files, dirs), (3, 1));
+        assert!(pages.is_empty());
+    }
+
+    #[tokio::test]
+    async fn finished_without_fids_keeps_polling() {
+        let waited = poll_save_task(3, Duration::ZERO, task(1, &[])).await;
+        assert!(matches!(waited, Err(QuarkError::Decode(_))));
+    }
+}
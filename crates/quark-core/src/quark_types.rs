@@ -0,0 +1,232 @@
+use serde::{Deserialize, Serialize};
+
+/// 夸克接口的统一响应外壳：{ status, code, message, data, metadata }
+#[derive(Debug, Deserialize)]
+pub struct ApiEnvelope<T> {
+    pub status: Option<i64>,
+    pub code: Option<i64>,
+    #[serde(default)]
+    pub message: String,
+    pub data: Option<T>,
+    #[serde(default)]
+    pub metadata: ListMetadata,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListMetadata {
+    #[serde(rename = "_total")]
+    pub total: Option<u64>,
+}
+
+// ── 分享 ─────────────────────────────────────────────────
+
+#[derive(Debug, Serialize)]
+pub struct ShareTokenRequest<'a> {
+    pub pwd_id: &'a str,
+    pub passcode: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShareTokenData {
+    pub stoken: String,
+    #[serde(default)]
+    pub title: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShareDetailQuery<'a> {
+    pub pwd_id: &'a str,
+    pub stoken: &'a str,
+    pub pdir_fid: &'a str,
+    pub force: u8,
+    #[serde(rename = "_page")]
+    pub page: u64,
+    #[serde(rename = "_size")]
+    pub size: u64,
+    #[serde(rename = "_fetch_total")]
+    pub fetch_total: u8,
+    #[serde(rename = "_fetch_sub_dirs")]
+    pub fetch_sub_dirs: u8,
+    #[serde(rename = "_sort")]
+    pub sort: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShareDetailData {
+    #[serde(default)]
+    pub list: Vec<ShareItem>,
+}
+
+/// 分享列表中的一项
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShareItem {
+    pub fid: String,
+    #[serde(default)]
+    pub file_name: String,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub dir: bool,
+    pub file_type: Option<i64>,
+    pub obj_category: Option<String>,
+    #[serde(default)]
+    pub format_type: String,
+    pub updated_at: Option<u64>,
+    pub l_updated_at: Option<u64>,
+    #[serde(default)]
+    pub share_fid_token: String,
+}
+
+impl ShareItem {
+    pub fn is_dir(&self) -> bool {
+        self.dir || self.file_type == Some(0) || self.obj_category.as_deref() == Some("dir")
+    }
+}
+
+// ── 转存 / 任务 ──────────────────────────────────────────
+
+#[derive(Debug, Serialize)]
+pub struct SaveRequest<'a> {
+    pub fid_list: &'a [String],
+    pub fid_token_list: &'a [String],
+    pub to_pdir_fid: &'a str,
+    pub pwd_id: &'a str,
+    pub stoken: &'a str,
+    pub pdir_fid: &'a str,
+    pub scene: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SaveData {
+    pub task_id: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaskQuery<'a> {
+    pub task_id: &'a str,
+    pub retry_index: u32,
+}
+
+/// 任务状态，status == 2 表示已完成
+#[derive(Debug, Deserialize)]
+pub struct TaskData {
+    #[serde(default)]
+    pub status: i64,
+    pub save_as: Option<SaveAs>,
+}
+
+impl TaskData {
+    pub const FINISHED: i64 = 2;
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SaveAs {
+    #[serde(default)]
+    pub save_as_top_fids: Vec<String>,
+}
+
+// ── 文件 ─────────────────────────────────────────────────
+
+#[derive(Debug, Serialize)]
+pub struct DownloadRequest<'a> {
+    pub fids: &'a [String],
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DownloadItem {
+    #[serde(default)]
+    pub fid: String,
+    #[serde(default)]
+    pub file_name: String,
+    #[serde(default)]
+    pub size: u64,
+    /// 文件 MD5，可能是十六进制或 base64
+    #[serde(default)]
+    pub md5: String,
+    #[serde(default)]
+    pub download_url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeleteRequest<'a> {
+    pub action_type: u8,
+    pub filelist: &'a [String],
+    pub exclude_fids: &'a [String],
+}
+
+// ── 账号 ─────────────────────────────────────────────────
+
+#[derive(Debug, Serialize)]
+pub struct MemberQuery<'a> {
+    pub fetch_subscribe: bool,
+    #[serde(rename = "_ch")]
+    pub channel: &'a str,
+    pub fetch_identity: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemberData {
+    #[serde(default)]
+    pub use_capacity: u64,
+    #[serde(default)]
+    pub total_capacity: u64,
+    pub member_type: Option<String>,
+}
+
+/// /account/info 的响应，与其他接口不同：code 是字符串 "OK"，并带 success 字段
+#[derive(Debug, Default, Deserialize)]
+pub struct AccountInfoResponse {
+    #[serde(default)]
+    pub success: bool,
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub code: String,
+    #[serde(default, alias = "msg", alias = "error")]
+    pub message: String,
+    pub data: Option<AccountInfoData>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AccountInfoData {
+    #[serde(default)]
+    pub nickname: String,
+    #[serde(default)]
+    pub mobilekps: String,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl AccountInfoResponse {
+    pub fn is_success(&self) -> bool {
+        self.success || self.code.eq_ignore_ascii_case("OK")
+    }
+
+    pub fn nickname(&self) -> &str {
+        self.data.as_ref().map(|d| d.nickname.trim()).unwrap_or("")
+    }
+
+    /// 响应中是否带有账号身份（昵称或手机号密文）
+    pub fn has_identity(&self) -> bool {
+        self.data
+            .as_ref()
+            .is_some_and(|d| !d.nickname.trim().is_empty() || !d.mobilekps.trim().is_empty())
+    }
+
+    /// data 中是否有任何字段
+    pub fn has_data(&self) -> bool {
+        self.data
+            .as_ref()
+            .is_some_and(|d| self.has_identity() || !d.extra.is_empty())
+    }
+}
+
+/// 兼容字符串或数字形式的 code 字段
+fn string_or_number<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(s) => s,
+        serde_json::Value::Number(n) => n.to_string(),
+        _ => String::new(),
+    })
+}
@@ -0,0 +1,189 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+/// 单个下载的控制状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadControl {
+    Running,
+    Paused,
+    /// 中断但保留部分数据（页面刷新、应用退出），下次下载同一文件时续传
+    Stopped,
+    /// 用户取消，部分数据一并删除
+    Cancelled,
+}
+
+/// 最近一次上报的进度，供 list_downloads 查询
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProgressSnapshot {
+    pub downloaded: u64,
+    pub total: u64,
+    pub speed: f64,
+}
+
+/// 一个正在进行的下载，由下载任务持有并轮询控制状态
+pub struct DownloadHandle {
+    pub id: String,
+    pub filename: String,
+    pub save_path: PathBuf,
+    control: watch::Sender<DownloadControl>,
+    progress: Mutex<ProgressSnapshot>,
+}
+
+impl DownloadHandle {
+    pub fn control(&self) -> DownloadControl {
+        *self.control.borrow()
+    }
+
+    /// 已停止或已取消，下载任务应尽快退出
+    pub fn is_cancelled(&self) -> bool {
+        matches!(
+            self.control(),
+            DownloadControl::Stopped | DownloadControl::Cancelled
+        )
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.control() == DownloadControl::Paused
+    }
+
+    /// 仅在运行中时暂停；已停止/取消的下载不能再被改回
+    pub fn pause(&self) -> bool {
+        self.control.send_if_modified(|c| {
+            if *c == DownloadControl::Running {
+                *c = DownloadControl::Paused;
+                return true;
+            }
+            false
+        })
+    }
+
+    pub fn resume(&self) -> bool {
+        self.control.send_if_modified(|c| {
+            if *c == DownloadControl::Paused {
+                *c = DownloadControl::Running;
+                return true;
+            }
+            false
+        })
+    }
+
+    pub fn stop(&self) {
+        self.finish_with(DownloadControl::Stopped);
+    }
+
+    pub fn cancel(&self) {
+        self.finish_with(DownloadControl::Cancelled);
+    }
+
+    fn finish_with(&self, target: DownloadControl) {
+        self.control.send_if_modified(|c| {
+            if matches!(*c, DownloadControl::Running | DownloadControl::Paused) {
+                *c = target;
+                return true;
+            }
+            false
+        });
+    }
+
+    /// 暂停期间挂起，直到恢复或被停止；返回 false 表示不应继续下载
+    pub async fn wait_while_paused(&self) -> bool {
+        let mut rx = self.control.subscribe();
+        loop {
+            match *rx.borrow_and_update() {
+                DownloadControl::Running => return true,
+                DownloadControl::Stopped | DownloadControl::Cancelled => return false,
+                DownloadControl::Paused => {}
+            }
+            if rx.changed().await.is_err() {
+                return false;
+            }
+        }
+    }
+
+    pub fn update_progress(&self, downloaded: u64, total: u64, speed: f64) {
+        if let Ok(mut p) = self.progress.lock() {
+            *p = ProgressSnapshot {
+                downloaded,
+                total,
+                speed,
+            };
+        }
+    }
+
+    pub fn info(&self) -> DownloadInfo {
+        DownloadInfo {
+            id: self.id.clone(),
+            filename: self.filename.clone(),
+            save_path: self.save_path.to_string_lossy().to_string(),
+            state: self.control(),
+            progress: self.progress.lock().map(|p| p.clone()).unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadInfo {
+    pub id: String,
+    pub filename: String,
+    pub save_path: String,
+    pub state: DownloadControl,
+    #[serde(flatten)]
+    pub progress: ProgressSnapshot,
+}
+
+/// 下载注册表：以 download_id 为键，替代全局 epoch，实现按文件暂停/恢复/取消
+#[derive(Default)]
+pub struct DownloadRegistry {
+    downloads: Mutex<HashMap<String, Arc<DownloadHandle>>>,
+}
+
+impl DownloadRegistry {
+    pub fn register(&self, id: &str, filename: &str, save_path: PathBuf) -> Arc<DownloadHandle> {
+        let (control, _) = watch::channel(DownloadControl::Running);
+        let handle = Arc::new(DownloadHandle {
+            id: id.to_string(),
+            filename: filename.to_string(),
+            save_path,
+            control,
+            progress: Mutex::new(ProgressSnapshot::default()),
+        });
+        if let Ok(mut downloads) = self.downloads.lock() {
+            downloads.insert(id.to_string(), Arc::clone(&handle));
+        }
+        handle
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<DownloadHandle>> {
+        self.downloads.lock().ok()?.get(id).cloned()
+    }
+
+    pub fn remove(&self, id: &str) {
+        if let Ok(mut downloads) = self.downloads.lock() {
+            downloads.remove(id);
+        }
+    }
+
+    pub fn list(&self) -> Vec<DownloadInfo> {
+        let Ok(downloads) = self.downloads.lock() else {
+            return Vec::new();
+        };
+        let mut list: Vec<DownloadInfo> = downloads.values().map(|h| h.info()).collect();
+        list.sort_by(|a, b| a.filename.cmp(&b.filename));
+        list
+    }
+
+    /// 中断全部下载并保留部分数据，返回受影响的数量
+    pub fn stop_all(&self) -> usize {
+        let Ok(downloads) = self.downloads.lock() else {
+            return 0;
+        };
+        for handle in downloads.values() {
+            handle.stop();
+        }
+        downloads.len()
+    }
+}
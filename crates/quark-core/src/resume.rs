@@ -0,0 +1,160 @@
+use crate::sanitize;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// 状态文件格式版本，不兼容时直接丢弃旧的部分数据
+const STATE_VERSION: u32 = 2;
+
+/// 原子写入时临时文件的后缀，状态文件名要为它留出长度
+const TMP_SUFFIX: &str = ".tmp";
+
+/// 单个分段的续传信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentState {
+    pub start: u64,
+    pub end: u64,
+    /// 已落盘的字节数（从 start 起连续）
+    pub written: u64,
+}
+
+impl SegmentState {
+    pub(crate) fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+}
+
+/// 持久化的下载状态，以隐藏文件的形式放在目标文件旁边。
+/// 不保存下载链接：夸克 CDN 链接会过期，续传时总是使用调用方重新获取的链接。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadState {
+    pub version: u32,
+    /// 来源文件 fid（分享内文件 ID），用于确认续传的是同一个文件
+    pub fid: String,
+    pub filename: String,
+    /// 预分配的目标文件，各段直接写入其中
+    pub save_path: PathBuf,
+    pub total_size: u64,
+    pub segments: Vec<SegmentState>,
+}
+
+impl DownloadState {
+    pub fn new(
+        fid: &str,
+        filename: &str,
+        save_path: &Path,
+        total_size: u64,
+        segments: Vec<SegmentState>,
+    ) -> Self {
+        Self {
+            version: STATE_VERSION,
+            fid: fid.to_string(),
+            filename: filename.to_string(),
+            save_path: save_path.to_path_buf(),
+            total_size,
+            segments,
+        }
+    }
+
+    /// 读取状态文件，不存在或无法解析时返回 None
+    pub async fn load(state_path: &Path) -> Option<Self> {
+        let raw = tokio::fs::read(state_path).await.ok()?;
+        let state: Self = serde_json::from_slice(&raw).ok()?;
+        if state.version != STATE_VERSION {
+            return None;
+        }
+        Some(state)
+    }
+
+    /// 原子写入状态文件（先写临时文件再 rename，避免崩溃时留下半截 JSON）
+    pub async fn save(&self, state_path: &Path) -> Result<(), String> {
+        let raw = serde_json::to_vec(self).map_err(|e| format!("序列化下载状态失败: {}", e))?;
+        let mut tmp = state_path.as_os_str().to_owned();
+        tmp.push(TMP_SUFFIX);
+        tokio::fs::write(&tmp, raw)
+            .await
+            .map_err(|e| format!("写入下载状态失败: {}", e))?;
+        tokio::fs::rename(&tmp, state_path)
+            .await
+            .map_err(|e| format!("写入下载状态失败: {}", e))
+    }
+
+    /// 判断已有的部分数据是否属于当前要下载的文件
+    pub fn matches(&self, fid: &str, total_size: u64) -> bool {
+        if self.total_size != total_size {
+            return false;
+        }
+        // 两边都带 fid 时必须一致；旧调用方没有传 fid 时退化为按文件名 + 大小匹配
+        self.fid.is_empty() || fid.is_empty() || self.fid == fid
+    }
+
+    pub fn written(&self) -> u64 {
+        self.segments.iter().map(|s| s.written).sum()
+    }
+
+    /// 目标文件丢失或长度不对时，已记录的进度不再可信，全部清零重下
+    pub async fn reconcile(&mut self) {
+        let on_disk = tokio::fs::metadata(&self.save_path)
+            .await
+            .map(|m| m.len())
+            .ok();
+        if on_disk != Some(self.total_size) {
+            for seg in &mut self.segments {
+                seg.written = 0;
+            }
+            return;
+        }
+        for seg in &mut self.segments {
+            seg.written = seg.written.min(seg.len());
+        }
+    }
+}
+
+/// 状态文件：<下载目录>/.<原始文件名>.quarkdl
+/// 按调用方给出的原始文件名定位，这样即便最终路径因重名被改成 "xxx (1)"，
+/// 下次下载同一文件时也能找到它。
+/// 文件名过长时截断，并给临时文件的 .tmp 后缀留出余量，保证两者都不超过文件系统的单名上限。
+pub fn state_path(downloads_dir: &Path, filename: &str) -> PathBuf {
+    let name = sanitize::truncate_name(
+        &format!(".{}.quarkdl", filename),
+        sanitize::MAX_NAME_BYTES - TMP_SUFFIX.len(),
+    );
+    sanitize::long_path(&downloads_dir.join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn long_multibyte_names_can_be_checkpointed() {
+        let dir = std::env::temp_dir().join(format!(
+            "quark-core-resume-{}",
+            uuid::Uuid::new_v4().simple()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        // 80 个汉字 = 240 字节，加上前后缀超过 255 字节的单名上限
+        let filename = format!("{}.mkv", "长".repeat(80));
+        let path = state_path(&dir, &filename);
+        let name_len = path.file_name().unwrap().len();
+        assert!(name_len + TMP_SUFFIX.len() <= sanitize::MAX_NAME_BYTES);
+        assert!(path.to_string_lossy().ends_with(".quarkdl"));
+
+        let state = DownloadState::new(
+            "fid-1",
+            &filename,
+            &dir.join(&filename),
+            100,
+            vec![SegmentState {
+                start: 0,
+                end: 99,
+                written: 10,
+            }],
+        );
+        state.save(&path).await.unwrap();
+        let loaded = DownloadState::load(&path).await.unwrap();
+        assert_eq!(loaded.filename, filename);
+        assert_eq!(loaded.written(), 10);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
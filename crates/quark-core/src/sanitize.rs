@@ -0,0 +1,293 @@
+use std::path::{Path, PathBuf};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// 大多数文件系统（NTFS / APFS / ext4）单级文件名的上限，按 UTF-8 字节计
+pub const MAX_NAME_BYTES: usize = 255;
+
+/// 超过这个长度的“扩展名”不当作扩展名保留，避免整段被当成扩展名而无从截断
+const MAX_EXTENSION_BYTES: usize = 16;
+
+/// Windows 保留设备名，不区分大小写，带任何扩展名都不可用
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// 清洗单级文件名 / 目录名，结果在 Windows、macOS、Linux 上都可直接使用：
+/// - 路径分隔符、`<>:"|?*` 与控制字符替换为 `_`
+/// - 去掉首尾空白与结尾的点（Windows 会静默丢弃它们）
+/// - Windows 保留名（CON、COM1 等）在主名后加 `_`
+/// - 空名、`.`、`..` 替换为 `_`
+/// - 超过 255 字节时按字素边界截断主名，保留扩展名
+pub fn sanitize_filename(name: &str) -> String {
+    let replaced: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let trimmed = replaced.trim().trim_end_matches(['.', ' ']);
+    if trimmed.is_empty() || trimmed == ".." {
+        return "_".to_string();
+    }
+
+    // 保留名按第一个点之前的部分判断，"AUX.tar.gz" 同样不可用
+    let base = trimmed.split('.').next().unwrap_or(trimmed);
+    let name = if is_reserved(base) {
+        format!("{}_{}", base, &trimmed[base.len()..])
+    } else {
+        trimmed.to_string()
+    };
+    truncate_name(&name, MAX_NAME_BYTES)
+}
+
+/// 把分享内的相对路径拆成逐级清洗后的名称。
+/// 含 `..` 的路径直接拒绝，不做“纠正”，以免悄悄写到别的目录里。
+pub fn sanitize_relative_path(path: &str) -> Result<Vec<String>, String> {
+    let mut parts = Vec::new();
+    for part in path.split(['/', '\\']) {
+        let part = part.trim();
+        if part.is_empty() || part == "." {
+            continue;
+        }
+        if part == ".." {
+            return Err(format!("路径中不允许出现 \"..\": {}", path));
+        }
+        parts.push(sanitize_filename(part));
+    }
+    Ok(parts)
+}
+
+/// 截断到 max_bytes 字节以内：只截主名、保留扩展名，且不会切开一个字素（如组合表情、带声调的字符）
+pub fn truncate_name(name: &str, max_bytes: usize) -> String {
+    if name.len() <= max_bytes {
+        return name.to_string();
+    }
+    let (stem, ext) = split_extension(name);
+    let ext_len = ext.map_or(0, |e| e.len() + 1);
+    if ext_len >= max_bytes {
+        return truncate_graphemes(name, max_bytes).to_string();
+    }
+    let stem = truncate_graphemes(stem, max_bytes - ext_len).trim_end_matches(['.', ' ']);
+    let stem = if stem.is_empty() { "_" } else { stem };
+    join_extension(stem, ext)
+}
+
+/// 在扩展名前插入后缀（如重名时的 " (1)"），必要时截断主名使总长仍不超限
+pub fn with_suffix(name: &str, suffix: &str) -> String {
+    let (stem, ext) = split_extension(name);
+    let ext_len = ext.map_or(0, |e| e.len() + 1);
+    let budget = MAX_NAME_BYTES.saturating_sub(ext_len + suffix.len());
+    let stem = truncate_graphemes(stem, budget);
+    join_extension(&format!("{}{}", stem, suffix), ext)
+}
+
+/// Windows 上把过长的绝对路径转成 `\\?\` 形式以绕过 260 字符的 MAX_PATH 限制，其他平台原样返回
+pub fn long_path(path: &Path) -> PathBuf {
+    #[cfg(windows)]
+    {
+        const MAX_PATH: usize = 260;
+        let raw = path.as_os_str().to_string_lossy();
+        if path.is_absolute() && raw.len() >= MAX_PATH - 12 && !raw.starts_with(r"\\?\") {
+            return match raw.strip_prefix(r"\\") {
+                Some(unc) => PathBuf::from(format!(r"\\?\UNC\{}", unc)),
+                None => PathBuf::from(format!(r"\\?\{}", raw.replace('/', "\\"))),
+            };
+        }
+    }
+    path.to_path_buf()
+}
+
+fn is_reserved(base: &str) -> bool {
+    let base = base.trim_end();
+    RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(base))
+}
+
+/// 拆出扩展名；以点开头的隐藏文件、过长或含空白的“扩展名”不算
+fn split_extension(name: &str) -> (&str, Option<&str>) {
+    match name.rfind('.') {
+        Some(i) if i > 0 => {
+            let ext = &name[i + 1..];
+            if ext.is_empty()
+                || ext.len() > MAX_EXTENSION_BYTES
+                || ext.contains(char::is_whitespace)
+            {
+                (name, None)
+            } else {
+                (&name[..i], Some(ext))
+            }
+        }
+        _ => (name, None),
+    }
+}
+
+fn join_extension(stem: &str, ext: Option<&str>) -> String {
+    match ext {
+        Some(ext) => format!("{}.{}", stem, ext),
+        None => stem.to_string(),
+    }
+}
+
+/// 取不超过 max_bytes 字节的最长字素前缀
+fn truncate_graphemes(s: &str, max_bytes: usize) -> &str {
+    let mut end = 0;
+    for (i, g) in s.grapheme_indices(true) {
+        if i + g.len() > max_bytes {
+            break;
+        }
+        end = i + g.len();
+    }
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 夸克分享中常见的真实文件名
+    const CORPUS: &[&str] = &[
+        "流浪地球2.2023.2160p.WEB-DL.H265.DDP5.1.mkv",
+        "【高清】狂飙 第01集.mp4",
+        "第01集：开端.mp4",
+        "考研数学 (2024版) 张宇/基础30讲.pdf",
+        "《三体》全集 刘慈欣.epub",
+        "周杰伦 - 晴天 [无损].flac",
+        "2023年度工作总结（终稿）.docx",
+        "新建文件夹",
+        "README",
+        ".quarkdl",
+    ];
+
+    fn is_portable(name: &str) -> bool {
+        !name.is_empty()
+            && name.len() <= MAX_NAME_BYTES
+            && !name.contains(['/', '\\', '<', '>', ':', '"', '|', '?', '*'])
+            && !name.chars().any(char::is_control)
+            && !name.ends_with(['.', ' '])
+            && name != ".."
+    }
+
+    #[test]
+    fn corpus_names_stay_portable() {
+        for raw in CORPUS {
+            let name = sanitize_filename(raw);
+            assert!(is_portable(&name), "{:?} -> {:?}", raw, name);
+        }
+    }
+
+    #[test]
+    fn legal_chinese_names_are_unchanged() {
+        for raw in CORPUS.iter().filter(|n| !n.contains('/')) {
+            assert_eq!(sanitize_filename(raw), *raw);
+        }
+    }
+
+    #[test]
+    fn replaces_illegal_characters() {
+        assert_eq!(sanitize_filename("第01集:开端?.mp4"), "第01集_开端_.mp4");
+        assert_eq!(
+            sanitize_filename("课件/讲义\\第一章.pdf"),
+            "课件_讲义_第一章.pdf"
+        );
+        assert_eq!(
+            sanitize_filename("问答<精选>|合集*.txt"),
+            "问答_精选__合集_.txt"
+        );
+        assert_eq!(sanitize_filename("带\"引号\".txt"), "带_引号_.txt");
+        assert_eq!(sanitize_filename("换行\n制表\t.txt"), "换行_制表_.txt");
+    }
+
+    #[test]
+    fn strips_trailing_dots_and_spaces() {
+        assert_eq!(sanitize_filename("未完待续..."), "未完待续");
+        assert_eq!(sanitize_filename("  空格结尾 . "), "空格结尾");
+        assert_eq!(sanitize_filename("..."), "_");
+        assert_eq!(sanitize_filename("   "), "_");
+    }
+
+    #[test]
+    fn renames_windows_reserved_names() {
+        assert_eq!(sanitize_filename("CON"), "CON_");
+        assert_eq!(sanitize_filename("con.txt"), "con_.txt");
+        assert_eq!(sanitize_filename("Aux.tar.gz"), "Aux_.tar.gz");
+        assert_eq!(sanitize_filename("COM1.mp4"), "COM1_.mp4");
+        assert_eq!(sanitize_filename("lpt9"), "lpt9_");
+        assert_eq!(sanitize_filename("CONSOLE.txt"), "CONSOLE.txt");
+        assert_eq!(sanitize_filename("控制台 CON.txt"), "控制台 CON.txt");
+    }
+
+    #[test]
+    fn truncates_long_chinese_names_keeping_extension() {
+        // 100 个汉字 = 300 字节
+        let raw = format!("{}.mp4", "长".repeat(100));
+        let name = sanitize_filename(&raw);
+        assert!(name.len() <= MAX_NAME_BYTES);
+        assert!(name.ends_with(".mp4"));
+        assert_eq!(name, format!("{}.mp4", "长".repeat(83)));
+    }
+
+    #[test]
+    fn truncation_never_splits_graphemes() {
+        // 家庭表情由多个码点组成，一个字素 18 字节
+        let family = "👨‍👩‍👧";
+        let raw = format!("{}.txt", family.repeat(20));
+        let name = sanitize_filename(&raw);
+        assert!(name.len() <= MAX_NAME_BYTES);
+        let stem = name.strip_suffix(".txt").unwrap();
+        assert_eq!(stem, family.repeat(stem.len() / family.len()));
+
+        // 组合声调：e + U+0301
+        let accented = "e\u{301}";
+        let name = truncate_name(&format!("{}.md", accented.repeat(100)), 50);
+        assert!(name.len() <= 50);
+        assert_eq!(name, format!("{}.md", accented.repeat(15)));
+    }
+
+    #[test]
+    fn long_extension_is_not_preserved() {
+        let raw = format!("{}.{}", "文".repeat(50), "x".repeat(200));
+        let name = sanitize_filename(&raw);
+        assert!(name.len() <= MAX_NAME_BYTES);
+        assert!(name.starts_with("文"));
+    }
+
+    #[test]
+    fn suffix_fits_within_limit() {
+        assert_eq!(with_suffix("报告.pdf", " (1)"), "报告 (1).pdf");
+        assert_eq!(with_suffix("无扩展名", " (2)"), "无扩展名 (2)");
+        let long = format!("{}.mkv", "剧".repeat(84));
+        let name = with_suffix(&long, " (12)");
+        assert!(name.len() <= MAX_NAME_BYTES);
+        assert!(name.ends_with(" (12).mkv"));
+    }
+
+    #[test]
+    fn relative_paths_are_split_and_cleaned() {
+        assert_eq!(
+            sanitize_relative_path("第一季/第01集：开端.mp4").unwrap(),
+            vec!["第一季", "第01集：开端.mp4"]
+        );
+        assert_eq!(
+            sanitize_relative_path("\\资料\\\\./课件?/a.pdf").unwrap(),
+            vec!["资料", "课件_", "a.pdf"]
+        );
+        assert_eq!(sanitize_relative_path("").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn relative_paths_reject_traversal() {
+        assert!(sanitize_relative_path("../etc/passwd").is_err());
+        assert!(sanitize_relative_path("第一季/../../外面.mp4").is_err());
+        assert!(sanitize_relative_path("第一季\\..\\外面.mp4").is_err());
+        // 名称里恰好含两个点不是路径穿越
+        assert!(sanitize_relative_path("第一季/a..b.mp4").is_ok());
+    }
+
+    #[test]
+    fn long_path_is_noop_for_short_paths() {
+        let path = Path::new("downloads").join("第一季").join("01.mp4");
+        assert_eq!(long_path(&path), path);
+    }
+}
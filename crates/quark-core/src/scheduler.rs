@@ -0,0 +1,267 @@
+use crate::resume::SegmentState;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// 最小块大小：切分和窃取都不会产生比它更小的块，避免小文件被切成大量碎片请求
+pub const MIN_BLOCK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// 刚开始下载的块还没有可信的速度样本，这段时间内不参与"最慢"评比
+const SPEED_WARMUP_SECS: f64 = 1.0;
+
+/// 一个连续的字节区间。`end` 会在被其他 worker 窃取后半段时缩小。
+pub struct Block {
+    pub start: u64,
+    end: AtomicU64,
+    /// 已收到的字节数（含尚未落盘的缓冲），窃取时以此为切分起点
+    received: AtomicU64,
+    /// 已落盘的字节数，续传状态只记录这个值
+    written: AtomicU64,
+    /// 是否有 worker 正在下载
+    active: AtomicBool,
+    /// 本轮开始下载的时间与当时已收到的字节数，用于估算速度
+    started: Mutex<(Instant, u64)>,
+    pub retries: AtomicU32,
+    pub last_error: Mutex<Option<String>>,
+}
+
+impl Block {
+    fn new(start: u64, end: u64, written: u64) -> Self {
+        Self {
+            start,
+            end: AtomicU64::new(end),
+            received: AtomicU64::new(written),
+            written: AtomicU64::new(written),
+            active: AtomicBool::new(false),
+            started: Mutex::new((Instant::now(), written)),
+            retries: AtomicU32::new(0),
+            last_error: Mutex::new(None),
+        }
+    }
+
+    pub fn end(&self) -> u64 {
+        self.end.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn len(&self) -> u64 {
+        self.end() - self.start + 1
+    }
+
+    pub fn written(&self) -> u64 {
+        self.written.load(Ordering::Relaxed).min(self.len())
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.written() >= self.len()
+    }
+
+    /// 更新已收到的字节数（从 start 起算）
+    pub fn set_received(&self, received: u64) {
+        self.received.store(received, Ordering::Relaxed);
+    }
+
+    /// 更新已落盘的字节数（从 start 起算）
+    pub fn set_written(&self, written: u64) {
+        self.written.store(written, Ordering::Relaxed);
+    }
+
+    pub fn record_error(&self, err: &str) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut last) = self.last_error.lock() {
+            *last = Some(err.to_string());
+        }
+    }
+
+    fn activate(&self) {
+        let received = self.written.load(Ordering::Relaxed);
+        self.received.store(received, Ordering::Relaxed);
+        if let Ok(mut started) = self.started.lock() {
+            *started = (Instant::now(), received);
+        }
+        self.active.store(true, Ordering::Release);
+    }
+
+    /// 预计剩余时间（秒）；尚无速度样本时返回 None
+    fn eta_secs(&self, now: Instant) -> Option<f64> {
+        let (since, base) = *self.started.lock().ok()?;
+        let elapsed = now.duration_since(since).as_secs_f64();
+        if elapsed < SPEED_WARMUP_SECS {
+            return None;
+        }
+        let received = self.received.load(Ordering::Relaxed);
+        let speed = received.saturating_sub(base) as f64 / elapsed;
+        let remaining = self.len().saturating_sub(received) as f64;
+        // 完全卡住的连接速度为 0，视为无限慢，优先被窃取
+        Some(if speed > 0.0 {
+            remaining / speed
+        } else {
+            f64::INFINITY
+        })
+    }
+}
+
+/// 动态分块调度器：worker 先领取未开始的块，领完后从预计最晚完成的在途块
+/// 切走剩余的一半，让快连接帮慢连接分担，整体速度逼近各快连接之和。
+pub struct Scheduler {
+    blocks: Mutex<Vec<Arc<Block>>>,
+    aborted: AtomicBool,
+}
+
+impl Scheduler {
+    pub fn new(segments: &[SegmentState]) -> Self {
+        let blocks = segments
+            .iter()
+            .map(|s| Arc::new(Block::new(s.start, s.end, s.written.min(s.len()))))
+            .collect();
+        Self {
+            blocks: Mutex::new(blocks),
+            aborted: AtomicBool::new(false),
+        }
+    }
+
+    /// 实际并发数：受线程数上限和最小块大小共同约束
+    pub fn worker_count(total_size: u64, thread_count: usize) -> usize {
+        let by_size = (total_size / MIN_BLOCK_SIZE).max(1);
+        (thread_count as u64).min(by_size) as usize
+    }
+
+    /// 首次下载的初始切分：每个 worker 一块，块大小随文件大小和并发数自适应
+    pub fn plan(total_size: u64, thread_count: usize) -> Vec<SegmentState> {
+        let workers = Self::worker_count(total_size, thread_count) as u64;
+        let block_size = total_size / workers;
+        (0..workers)
+            .map(|i| {
+                let start = i * block_size;
+                let end = if i == workers - 1 {
+                    total_size - 1
+                } else {
+                    (i + 1) * block_size - 1
+                };
+                SegmentState {
+                    start,
+                    end,
+                    written: 0,
+                }
+            })
+            .collect()
+    }
+
+    /// 领取下一块：优先未开始/未完成的空闲块，没有则窃取最慢在途块的后半段。
+    /// 返回块序号（用于日志和进度上报）。
+    pub fn next_block(&self) -> Option<(usize, Arc<Block>)> {
+        if self.is_aborted() {
+            return None;
+        }
+        let mut blocks = self.blocks.lock().ok()?;
+
+        if let Some((i, block)) = blocks
+            .iter()
+            .enumerate()
+            .find(|(_, b)| !b.active.load(Ordering::Acquire) && !b.is_complete())
+        {
+            block.activate();
+            return Some((i, Arc::clone(block)));
+        }
+
+        let now = Instant::now();
+        let victim = blocks
+            .iter()
+            .filter(|b| b.active.load(Ordering::Acquire))
+            .filter_map(|b| {
+                let remaining = b.len().saturating_sub(b.received.load(Ordering::Relaxed));
+                if remaining < 2 * MIN_BLOCK_SIZE {
+                    return None;
+                }
+                b.eta_secs(now).map(|eta| (Arc::clone(b), remaining, eta))
+            })
+            .max_by(|a, b| a.2.total_cmp(&b.2));
+
+        let (victim, remaining, _) = victim?;
+        let old_end = victim.end();
+        let split = old_end + 1 - remaining / 2;
+        victim.end.store(split - 1, Ordering::Release);
+
+        let stolen = Arc::new(Block::new(split, old_end, 0));
+        stolen.activate();
+        blocks.push(Arc::clone(&stolen));
+        Some((blocks.len() - 1, stolen))
+    }
+
+    /// worker 结束（完成或失败）后释放块，未完成的部分可被其他 worker 重新领取
+    pub fn release(&self, block: &Block) {
+        block.active.store(false, Ordering::Release);
+    }
+
+    /// 某块重试耗尽后让其他 worker 在当前块结束后不再领取新块
+    pub fn abort(&self) {
+        self.aborted.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.aborted.load(Ordering::Relaxed)
+    }
+
+    pub fn blocks(&self) -> Vec<Arc<Block>> {
+        self.blocks.lock().map(|b| b.clone()).unwrap_or_default()
+    }
+
+    /// 已落盘的总字节数
+    pub fn written(&self) -> u64 {
+        self.blocks().iter().map(|b| b.written()).sum()
+    }
+
+    /// 完整性检查：按已落盘的字节数找出没有被任何块覆盖的区间（闭区间）。
+    /// 既能发现块内没写满的尾部，也能发现块划分本身的缺口。
+    pub fn missing_ranges(&self, total_size: u64) -> Vec<(u64, u64)> {
+        let mut covered: Vec<(u64, u64)> = self
+            .blocks()
+            .iter()
+            .filter(|b| b.written() > 0)
+            .map(|b| (b.start, b.start + b.written()))
+            .collect();
+        covered.sort_unstable();
+
+        let mut gaps = Vec::new();
+        let mut cursor = 0;
+        for (start, end) in covered {
+            if start > cursor {
+                gaps.push((cursor, start - 1));
+            }
+            cursor = cursor.max(end);
+        }
+        if cursor < total_size {
+            gaps.push((cursor, total_size - 1));
+        }
+        gaps
+    }
+
+    /// 把缺失区间重新排队：已有的块收缩到已落盘的部分，缺失区间作为新块供下一轮 worker 领取
+    pub fn requeue(&self, ranges: &[(u64, u64)]) {
+        let Ok(mut blocks) = self.blocks.lock() else {
+            return;
+        };
+        blocks.retain(|b| b.written() > 0);
+        for block in blocks.iter().filter(|b| !b.is_complete()) {
+            block
+                .end
+                .store(block.start + block.written() - 1, Ordering::Release);
+        }
+        blocks.extend(
+            ranges
+                .iter()
+                .map(|&(start, end)| Arc::new(Block::new(start, end, 0))),
+        );
+    }
+
+    /// 当前各块的续传快照
+    pub fn snapshot(&self) -> Vec<SegmentState> {
+        self.blocks()
+            .iter()
+            .map(|b| SegmentState {
+                start: b.start,
+                end: b.end(),
+                written: b.written(),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,178 @@
+use crate::quark_client::QuarkError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// 一个登录会话的 Cookie，按名称去重，后写入的覆盖先写入的
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CookieJar {
+    cookies: BTreeMap<String, String>,
+}
+
+impl CookieJar {
+    /// 从 "a=1; b=2" 形式的 Cookie 请求头解析
+    pub fn parse(raw: &str) -> Self {
+        let mut jar = Self::default();
+        for pair in raw.split(';') {
+            jar.insert(pair);
+        }
+        jar
+    }
+
+    /// 合并一条 Set-Cookie；值为空或 Max-Age<=0 表示服务端要求删除。返回 Cookie 是否有变化
+    pub fn merge_set_cookie(&mut self, set_cookie: &str) -> bool {
+        let mut parts = set_cookie.split(';');
+        let pair = parts.next().unwrap_or("");
+        let expired = parts.any(|attr| {
+            let attr = attr.trim().to_ascii_lowercase();
+            attr.strip_prefix("max-age=")
+                .and_then(|v| v.trim().parse::<i64>().ok())
+                .is_some_and(|age| age <= 0)
+        });
+        match pair.split_once('=') {
+            Some((name, value)) if expired || value.trim().is_empty() => {
+                self.cookies.remove(name.trim()).is_some()
+            }
+            _ => self.insert(pair),
+        }
+    }
+
+    fn insert(&mut self, pair: &str) -> bool {
+        let Some((name, value)) = pair.trim().split_once('=') else {
+            return false;
+        };
+        let (name, value) = (name.trim(), value.trim());
+        if name.is_empty() {
+            return false;
+        }
+        self.cookies
+            .insert(name.to_string(), value.to_string())
+            .as_deref()
+            != Some(value)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cookies.is_empty()
+    }
+
+    /// 拼成 Cookie 请求头
+    pub fn header(&self) -> String {
+        self.cookies
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+/// 服务端保存的登录会话：前端只持有不透明的会话 ID，Cookie 不再经过 webview。
+/// 持久化到 app 数据目录下的 sessions.json，重启后无需重新登录
+pub struct SessionStore {
+    path: PathBuf,
+    sessions: Mutex<HashMap<String, CookieJar>>,
+}
+
+impl SessionStore {
+    /// 读取会话文件，不存在或无法解析时从空开始
+    pub fn open(path: PathBuf) -> Self {
+        let sessions = std::fs::read(&path)
+            .ok()
+            .and_then(|raw| serde_json::from_slice(&raw).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            sessions: Mutex::new(sessions),
+        }
+    }
+
+    /// 新建会话并返回会话 ID
+    pub fn create(&self, jar: CookieJar) -> Result<String, String> {
+        if jar.is_empty() {
+            return Err("Cookie 为空".into());
+        }
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let mut sessions = self.sessions.lock().map_err(|_| "会话被占用".to_string())?;
+        sessions.insert(id.clone(), jar);
+        self.persist(&sessions)?;
+        Ok(id)
+    }
+
+    /// 会话对应的 Cookie 请求头；ID 为空表示未登录，返回空 Cookie
+    pub fn cookie(&self, id: &str) -> Result<String, QuarkError> {
+        if id.is_empty() {
+            return Ok(String::new());
+        }
+        self.sessions
+            .lock()
+            .ok()
+            .and_then(|sessions| sessions.get(id).map(CookieJar::header))
+            .ok_or_else(|| QuarkError::AuthExpired("登录会话不存在".into()))
+    }
+
+    /// 把响应里的 Set-Cookie 全部合并进会话，有变化时写盘
+    pub fn merge<'a>(&self, id: &str, set_cookies: impl IntoIterator<Item = &'a str>) {
+        let Ok(mut sessions) = self.sessions.lock() else {
+            return;
+        };
+        let Some(jar) = sessions.get_mut(id) else {
+            return;
+        };
+        let mut changed = false;
+        for set_cookie in set_cookies {
+            changed |= jar.merge_set_cookie(set_cookie);
+        }
+        if changed {
+            if let Err(e) = self.persist(&sessions) {
+                log!("[session] {}", e);
+            }
+        }
+    }
+
+    /// 删除会话（退出登录）
+    pub fn remove(&self, id: &str) -> Result<(), String> {
+        let mut sessions = self.sessions.lock().map_err(|_| "会话被占用".to_string())?;
+        if sessions.remove(id).is_some() {
+            self.persist(&sessions)?;
+        }
+        Ok(())
+    }
+
+    fn persist(&self, sessions: &HashMap<String, CookieJar>) -> Result<(), String> {
+        let raw = serde_json::to_vec(sessions).map_err(|e| format!("序列化会话失败: {}", e))?;
+        let mut tmp = self.path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, raw).map_err(|e| format!("写入会话失败: {}", e))?;
+        std::fs::rename(&tmp, &self.path).map_err(|e| format!("写入会话失败: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_keeps_last_value_per_name() {
+        let jar = CookieJar::parse(" b=2; a=1;; =x; junk; a=3 ");
+        assert_eq!(jar.header(), "a=3; b=2");
+    }
+
+    #[test]
+    fn set_cookie_updates_and_adds() {
+        let mut jar = CookieJar::parse("__pus=abc; __puus=old");
+        assert!(jar.merge_set_cookie("__puus=new; Path=/; Domain=.quark.cn; HttpOnly"));
+        assert!(jar.merge_set_cookie("__kp=1; Max-Age=3600"));
+        assert!(!jar.merge_set_cookie("__kp=1; Max-Age=3600"));
+        assert_eq!(jar.header(), "__kp=1; __pus=abc; __puus=new");
+    }
+
+    #[test]
+    fn set_cookie_can_delete() {
+        let mut jar = CookieJar::parse("a=1; b=2; c=3");
+        assert!(jar.merge_set_cookie("a=; Path=/"));
+        assert!(jar.merge_set_cookie("b=gone; Max-Age=0"));
+        assert!(!jar.merge_set_cookie("missing=; Max-Age=0"));
+        assert_eq!(jar.header(), "c=3");
+    }
+}
@@ -0,0 +1,232 @@
+use crate::downloader;
+use crate::proxy;
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// 目标位置已有同名文件时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// 另存为 "xxx (1)"、"xxx (2)"……
+    #[default]
+    Rename,
+    /// 直接覆盖
+    Overwrite,
+    /// 大小一致时跳过，否则另存
+    SkipSameSize,
+    /// 内容 MD5 一致时跳过，否则另存
+    SkipSameHash,
+    /// 已有文件比远端短时从其末尾续传，一样大时跳过，否则覆盖
+    Resume,
+}
+
+/// 按时段覆盖全局限速，时间为本地时间 "HH:MM"；start 晚于 end 时表示跨午夜
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleRule {
+    pub start: String,
+    pub end: String,
+    /// 该时段的全局限速（字节/秒），为空表示不限速
+    pub limit: Option<u64>,
+}
+
+impl ScheduleRule {
+    pub fn contains(&self, now: NaiveTime) -> bool {
+        let (Some(start), Some(end)) = (parse_time(&self.start), parse_time(&self.end)) else {
+            return false;
+        };
+        if start <= end {
+            start <= now && now < end
+        } else {
+            now >= start || now < end
+        }
+    }
+}
+
+/// 带宽限制，单位均为字节/秒，为空或 0 表示不限速
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BandwidthSettings {
+    /// 所有下载合计的上限，不在任何时段规则内时生效
+    pub global_limit: Option<u64>,
+    /// 单个下载的上限
+    pub per_download_limit: Option<u64>,
+    /// 时段规则，按顺序取第一个匹配的
+    pub schedule: Vec<ScheduleRule>,
+}
+
+impl BandwidthSettings {
+    /// 当前时刻生效的全局限速
+    pub fn global_limit_at(&self, now: NaiveTime) -> Option<u64> {
+        match self.schedule.iter().find(|r| r.contains(now)) {
+            Some(rule) => rule.limit,
+            None => self.global_limit,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for rule in &self.schedule {
+            for time in [&rule.start, &rule.end] {
+                if parse_time(time).is_none() {
+                    return Err(format!("限速时段格式错误（应为 HH:MM）: {}", time));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_time(raw: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(raw.trim(), "%H:%M").ok()
+}
+
+/// 一个出站代理。url 支持 http://、https://、socks5://、socks5h://（由代理解析域名）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProxyConfig {
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// 不走代理的主机，逗号分隔，如 "localhost,127.0.0.1,.corp.example.com"
+    pub no_proxy: Option<String>,
+}
+
+/// CDN 下载流量走哪个代理
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CdnProxyMode {
+    /// 与 API 流量相同
+    #[default]
+    SameAsApi,
+    /// 直连，不使用任何代理
+    Direct,
+    /// 使用 cdn 字段单独配置的代理
+    Custom,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProxySettings {
+    /// 夸克 API 与扫码登录使用的代理，为空时沿用系统环境变量（HTTP_PROXY 等）
+    pub api: Option<ProxyConfig>,
+    pub cdn_mode: CdnProxyMode,
+    pub cdn: Option<ProxyConfig>,
+}
+
+impl ProxySettings {
+    fn validate(&self) -> Result<(), String> {
+        for config in [&self.api, &self.cdn].into_iter().flatten() {
+            proxy::build(config)?;
+        }
+        if self.cdn_mode == CdnProxyMode::Custom && self.cdn.is_none() {
+            return Err("CDN 代理设为单独配置时必须填写代理地址".into());
+        }
+        Ok(())
+    }
+}
+
+/// 夸克 API 请求的超时、重试与限速（不影响 CDN 下载）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkSettings {
+    /// 建立连接的超时（秒）
+    pub connect_timeout_secs: u64,
+    /// 两次读到数据之间的最长间隔（秒），超过即视为连接挂起
+    pub read_timeout_secs: u64,
+    /// 失败后的最大重试次数
+    pub max_retries: u32,
+    /// 每个主机每秒最多发出的请求数，0 表示不限
+    pub requests_per_second: f64,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            connect_timeout_secs: 15,
+            read_timeout_secs: 30,
+            max_retries: 3,
+            requests_per_second: 10.0,
+        }
+    }
+}
+
+impl NetworkSettings {
+    fn validate(&self) -> Result<(), String> {
+        if self.connect_timeout_secs == 0 || self.read_timeout_secs == 0 {
+            return Err("超时时间必须大于 0 秒".into());
+        }
+        if !self.requests_per_second.is_finite() || self.requests_per_second < 0.0 {
+            return Err("每秒请求数不能为负".into());
+        }
+        if self.max_retries > 10 {
+            return Err("API 重试次数最多 10 次".into());
+        }
+        Ok(())
+    }
+}
+
+/// 全局下载设置，持久化到 app 数据目录下的 settings.json
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// 默认保存目录，为空时用系统下载目录
+    pub download_dir: Option<String>,
+    pub conflict_policy: ConflictPolicy,
+    pub bandwidth: BandwidthSettings,
+    pub proxy: ProxySettings,
+    pub network: NetworkSettings,
+}
+
+pub struct SettingsStore {
+    path: PathBuf,
+    current: Mutex<Settings>,
+}
+
+impl SettingsStore {
+    /// 读取设置文件，不存在或无法解析时使用默认值
+    pub fn open(path: PathBuf) -> Self {
+        let current = std::fs::read(&path)
+            .ok()
+            .and_then(|raw| serde_json::from_slice(&raw).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            current: Mutex::new(current),
+        }
+    }
+
+    pub fn get(&self) -> Settings {
+        self.current.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    /// 替换并保存设置
+    pub fn save(&self, settings: Settings) -> Result<Settings, String> {
+        settings.bandwidth.validate()?;
+        settings.proxy.validate()?;
+        settings.network.validate()?;
+        let raw =
+            serde_json::to_vec_pretty(&settings).map_err(|e| format!("序列化设置失败: {}", e))?;
+        let mut tmp = self.path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, raw).map_err(|e| format!("写入设置失败: {}", e))?;
+        std::fs::rename(&tmp, &self.path).map_err(|e| format!("写入设置失败: {}", e))?;
+        let mut current = self.current.lock().map_err(|_| "设置被占用".to_string())?;
+        *current = settings.clone();
+        Ok(settings)
+    }
+
+    /// 下载根目录：任务指定的目录 → 全局设置 → 系统下载目录
+    pub fn download_root(&self, per_job: Option<&str>) -> PathBuf {
+        let global = self.get().download_dir;
+        let dir = per_job
+            .filter(|d| !d.trim().is_empty())
+            .or(global.as_deref());
+        downloader::resolve_root(dir)
+    }
+
+    /// 冲突策略：任务指定的优先，否则用全局设置
+    pub fn conflict_policy(&self, per_job: Option<ConflictPolicy>) -> ConflictPolicy {
+        per_job.unwrap_or_else(|| self.get().conflict_policy)
+    }
+}
@@ -0,0 +1,294 @@
+//! 下载引擎的集成测试：不依赖窗口，本地起一个 HTTP 服务充当 CDN，进度通过通道收集
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use md5::{Digest, Md5};
+use quark_core::bandwidth::BandwidthLimit;
+use quark_core::downloader::{self, DownloadCtx};
+use quark_core::limiter::ConcurrencyLimit;
+use quark_core::progress::{DownloadProgress, ProgressStatus};
+use quark_core::registry::DownloadRegistry;
+use quark_core::settings::{BandwidthSettings, ConflictPolicy};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+const DOWNLOAD_ID: &str = "it-1";
+const FILENAME: &str = "sample.bin";
+
+struct Cdn {
+    body: Vec<u8>,
+    ranges: bool,
+}
+
+/// 支持单段 Range 的静态文件；ranges 为 false 时模拟不支持 Range 的 CDN
+async fn serve(State(cdn): State<Arc<Cdn>>, headers: HeaderMap) -> Response {
+    let total = cdn.body.len();
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("bytes="))
+        .and_then(|v| v.split_once('-'))
+        .filter(|_| cdn.ranges);
+    let mut resp = match range {
+        Some((start, end)) => {
+            let start: usize = start.parse().unwrap();
+            let end = end.parse::<usize>().map_or(total - 1, |e| e.min(total - 1));
+            let mut resp = cdn.body[start..=end].to_vec().into_response();
+            *resp.status_mut() = StatusCode::PARTIAL_CONTENT;
+            resp.headers_mut().insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&format!("bytes {}-{}/{}", start, end, total)).unwrap(),
+            );
+            resp
+        }
+        None => cdn.body.clone().into_response(),
+    };
+    if cdn.ranges {
+        resp.headers_mut()
+            .insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    }
+    resp
+}
+
+async fn start_cdn(body: Vec<u8>, ranges: bool) -> String {
+    let app = Router::new()
+        .route("/file", get(serve))
+        .with_state(Arc::new(Cdn { body, ranges }));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}/file", addr)
+}
+
+/// 可复现的伪随机内容，避免全零数据掩盖分段错位
+fn sample(len: usize) -> Vec<u8> {
+    let mut state: u32 = 0x2545_f491;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (state >> 24) as u8
+        })
+        .collect()
+}
+
+fn md5_hex(data: &[u8]) -> String {
+    Md5::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("quark-core-it-{}", uuid::Uuid::new_v4().simple()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+async fn download(
+    url: String,
+    dir: &Path,
+    remote_md5: Option<String>,
+) -> (
+    Result<serde_json::Value, String>,
+    PathBuf,
+    Vec<DownloadProgress>,
+) {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let (save_path, state_path) =
+        downloader::prepare_target(dir, FILENAME, ConflictPolicy::Rename).await;
+    let registry = DownloadRegistry::default();
+    let ctx = DownloadCtx {
+        progress: Arc::new(tx),
+        client: reqwest::Client::new(),
+        cookie: String::new(),
+        filename: FILENAME.into(),
+        fid: "fid-1".into(),
+        save_path: save_path.clone(),
+        state_path,
+        handle: registry.register(DOWNLOAD_ID, FILENAME, save_path.clone()),
+        download_id: DOWNLOAD_ID.into(),
+        thread_count: 4,
+        max_retries: downloader::DEFAULT_SEGMENT_RETRIES,
+        connections: Arc::new(ConcurrencyLimit::new(8)),
+        throttle: Arc::new(BandwidthLimit::new(BandwidthSettings::default())).throttle(),
+        conflict: ConflictPolicy::Rename,
+        remote_md5,
+    };
+    let result = downloader::do_download(ctx, url).await;
+    let mut events = Vec::new();
+    while let Ok(event) = rx.try_recv() {
+        events.push(event);
+    }
+    (result, save_path, events)
+}
+
+#[tokio::test]
+async fn multithread_download_is_verified_and_reported() {
+    // 超过多线程下载的 10MB 门槛，且不是块大小的整数倍
+    let body = sample(12 * 1024 * 1024 + 12_345);
+    let url = start_cdn(body.clone(), true).await;
+    let dir = temp_dir();
+
+    let (result, save_path, events) = download(url, &dir, Some(md5_hex(&body))).await;
+    let result = result.unwrap();
+    assert_eq!(result["path"], save_path.to_string_lossy().as_ref());
+    assert_eq!(std::fs::read(&save_path).unwrap(), body);
+
+    assert!(events
+        .iter()
+        .all(|e| e.id == DOWNLOAD_ID && e.filename == FILENAME));
+    assert!(events.iter().any(|e| e.status == ProgressStatus::Verifying));
+    let last = events.last().unwrap();
+    assert_eq!(last.status, ProgressStatus::Done);
+    assert_eq!(
+        (last.downloaded, last.total),
+        (body.len() as u64, body.len() as u64)
+    );
+    // 完成后续传状态文件应已清理，只剩下载的文件
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn falls_back_to_single_thread_without_ranges() {
+    let body = sample(300 * 1024);
+    let url = start_cdn(body.clone(), false).await;
+    let dir = temp_dir();
+
+    let (result, save_path, events) = download(url, &dir, None).await;
+    result.unwrap();
+    assert_eq!(std::fs::read(&save_path).unwrap(), body);
+    assert_eq!(events.last().unwrap().status, ProgressStatus::Done);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn md5_mismatch_removes_the_file() {
+    let body = sample(64 * 1024);
+    let url = start_cdn(body, true).await;
+    let dir = temp_dir();
+
+    let (result, save_path, events) = download(url, &dir, Some("0".repeat(32))).await;
+    assert!(result.unwrap_err().contains("MD5"));
+    assert!(!save_path.exists());
+    assert!(events.iter().all(|e| e.status != ProgressStatus::Done));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// 手写的慢速 CDN：每 50ms 吐出一小段，便于在下载中途暂停 / 取消
+async fn start_trickle_cdn(body: Vec<u8>) -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    let body = Arc::new(body);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let body = Arc::clone(&body);
+            tokio::spawn(async move {
+                let mut buf = vec![0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                let start = request
+                    .lines()
+                    .find_map(|l| l.strip_prefix("range: bytes="))
+                    .and_then(|r| r.split('-').next())
+                    .and_then(|s| s.trim().parse::<usize>().ok());
+                let total = body.len();
+                let head = match start {
+                    Some(start) => format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes {}-{}/{}\r\nAccept-Ranges: bytes\r\n\r\n",
+                        total - start,
+                        start,
+                        total - 1,
+                        total
+                    ),
+                    None => format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\n\r\n",
+                        total
+                    ),
+                };
+                if socket.write_all(head.as_bytes()).await.is_err() {
+                    return;
+                }
+                for chunk in body[start.unwrap_or(0)..].chunks(256) {
+                    if socket.write_all(chunk).await.is_err() {
+                        return;
+                    }
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                }
+            });
+        }
+    });
+    format!("http://{}/file", addr)
+}
+
+/// 已有部分文件时按 Resume 续传，下载途中执行 interrupt，返回下载结果与保存路径
+async fn interrupted_resume(
+    interrupt: fn(&quark_core::registry::DownloadHandle),
+) -> (Result<serde_json::Value, String>, PathBuf, Vec<u8>, PathBuf) {
+    let body = sample(64 * 1024);
+    let prefix = body[..4096].to_vec();
+    let url = start_trickle_cdn(body).await;
+    let dir = temp_dir();
+    std::fs::write(dir.join(FILENAME), &prefix).unwrap();
+
+    let (tx, _rx) = mpsc::unbounded_channel();
+    let (save_path, state_path) =
+        downloader::prepare_target(&dir, FILENAME, ConflictPolicy::Resume).await;
+    let registry = DownloadRegistry::default();
+    let handle = registry.register(DOWNLOAD_ID, FILENAME, save_path.clone());
+    let ctx = DownloadCtx {
+        progress: Arc::new(tx),
+        client: reqwest::Client::new(),
+        cookie: String::new(),
+        filename: FILENAME.into(),
+        fid: "fid-1".into(),
+        save_path: save_path.clone(),
+        state_path,
+        handle: Arc::clone(&handle),
+        download_id: DOWNLOAD_ID.into(),
+        thread_count: 4,
+        max_retries: downloader::DEFAULT_SEGMENT_RETRIES,
+        connections: Arc::new(ConcurrencyLimit::new(8)),
+        throttle: Arc::new(BandwidthLimit::new(BandwidthSettings::default())).throttle(),
+        conflict: ConflictPolicy::Resume,
+        remote_md5: None,
+    };
+    let download = tokio::spawn(downloader::do_download(ctx, url));
+    tokio::time::sleep(std::time::Duration::from_millis(400)).await;
+    interrupt(&handle);
+    let result = download.await.unwrap();
+    (result, save_path, prefix, dir)
+}
+
+#[tokio::test]
+async fn stopping_single_thread_resume_keeps_partial_data() {
+    let (result, save_path, prefix, dir) = interrupted_resume(|h| h.stop()).await;
+    assert!(result.unwrap_err().contains("中断"));
+    let kept = std::fs::read(&save_path).unwrap();
+    assert!(kept.len() > prefix.len());
+    assert_eq!(&kept[..prefix.len()], &prefix[..]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn cancelling_single_thread_resume_keeps_existing_data() {
+    let (result, save_path, prefix, dir) = interrupted_resume(|h| h.cancel()).await;
+    assert!(result.unwrap_err().contains("取消"));
+    // 续传前已有的数据不属于这次下载，取消时不能删
+    let kept = std::fs::read(&save_path).unwrap();
+    assert_eq!(&kept[..prefix.len()], &prefix[..]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,902 @@
+//! quark-dl：无界面的命令行下载工具，供无桌面环境的服务器脚本化批量下载。
+//! 与桌面应用共用客户端、下载引擎和 settings.json（代理、带宽、冲突策略等）
+
+use quark_core::bandwidth::BandwidthLimit;
+use quark_core::clients::HttpClients;
+use quark_core::downloader::{self, DownloadCtx};
+use quark_core::limiter::ConcurrencyLimit;
+use quark_core::progress::{DownloadProgress, ProgressReporter, ProgressStatus};
+use quark_core::qr_login::{QrLogin, QR_LIFETIME};
+use quark_core::quark_client::{self, QuarkError, ShareNode};
+use quark_core::quark_types::DownloadItem;
+use quark_core::registry::DownloadRegistry;
+use quark_core::session::{CookieJar, SessionStore};
+use quark_core::settings::SettingsStore;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "\
+用法: quark-dl [-v] <命令> [参数]
+
+命令:
+  login [--invert]                 在终端显示二维码，用夸克 APP 扫码登录；浅色背景终端加 --invert
+  login --cookie <Cookie|->        用浏览器里复制的 Cookie 登录，- 表示从标准输入读取
+  ls <分享链接>                    列出分享内的文件
+  get <分享链接> [选项]            转存并下载分享内的文件
+      --include <glob>             只下载匹配的文件（匹配相对路径或文件名），可重复
+      --out <目录>                 保存目录，默认用桌面应用设置的下载目录
+      --threads <N>                单个文件的下载线程数，默认 16
+  whoami                           查看当前登录的账号与网盘容量
+  logout                           退出登录
+
+选项:
+  -v, --verbose                    输出调试日志
+  -h, --help                       显示本帮助
+
+退出码: 0 成功，1 出错，2 参数错误，3 未登录或登录已失效，4 部分文件下载失败，130 被中断
+
+环境变量:
+  QUARK_DL_DATA_DIR                数据目录，默认与桌面应用相同";
+
+/// 与桌面应用的 app 数据目录一致（tauri.conf.json 的 identifier）
+const APP_IDENTIFIER: &str = "com.quark.downloader";
+const DEFAULT_THREADS: usize = 16;
+const MAX_THREADS: usize = 256;
+/// 所有文件共享的连接数上限，与桌面应用队列的默认值相同
+const MAX_CONNECTIONS: usize = 64;
+/// 进度行刷新间隔
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 不带参数值的开关选项
+const SWITCHES: &[&str] = &["invert"];
+
+const EXIT_ERROR: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+const EXIT_AUTH: u8 = 3;
+const EXIT_PARTIAL: u8 = 4;
+const EXIT_INTERRUPTED: u8 = 130;
+
+/// 命令失败：退出码 + 提示信息
+#[derive(Debug)]
+struct Failure {
+    code: u8,
+    message: String,
+}
+
+impl Failure {
+    fn new(code: u8, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn usage(message: impl Into<String>) -> Self {
+        Self::new(EXIT_USAGE, message)
+    }
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Self::new(EXIT_ERROR, message)
+    }
+}
+
+impl From<QuarkError> for Failure {
+    fn from(e: QuarkError) -> Self {
+        let code = match e {
+            QuarkError::AuthExpired(_) => EXIT_AUTH,
+            _ => EXIT_ERROR,
+        };
+        Self::new(code, e.to_string())
+    }
+}
+
+#[derive(Debug)]
+enum Command {
+    Login {
+        /// 为空时扫码登录
+        cookie: Option<String>,
+        invert: bool,
+    },
+    Ls {
+        url: String,
+    },
+    Get {
+        url: String,
+        include: Vec<glob::Pattern>,
+        out: Option<String>,
+        threads: usize,
+    },
+    Whoami,
+    Logout,
+}
+
+/// 解析命令行；返回 None 表示只需打印帮助
+fn parse_args(args: Vec<String>) -> Result<Option<(Command, bool)>, Failure> {
+    let (mut verbose, mut help) = (false, false);
+    let mut name = None;
+    let mut positional = Vec::new();
+    let mut options: Vec<(String, String)> = Vec::new();
+    // 缺少参数值的选项只可能是最后一个参数；先记下，帮助选项优先
+    let mut missing = None;
+    let mut rest = args.into_iter();
+    while let Some(arg) = rest.next() {
+        // -v / -h 只在选项位置生效，作为 --out 等选项的值时原样保留
+        match arg.as_str() {
+            "-v" | "--verbose" => verbose = true,
+            "-h" | "--help" => help = true,
+            _ if name.is_none() => name = Some(arg),
+            _ => {
+                let Some(flag) = arg.strip_prefix("--") else {
+                    positional.push(arg);
+                    continue;
+                };
+                let (key, value) = match flag.split_once('=') {
+                    Some((key, value)) => (key.to_string(), value.to_string()),
+                    None if SWITCHES.contains(&flag) => (flag.to_string(), String::new()),
+                    None => match rest.next() {
+                        Some(value) => (flag.to_string(), value),
+                        None => {
+                            missing = Some(flag.to_string());
+                            break;
+                        }
+                    },
+                };
+                options.push((key, value));
+            }
+        }
+    }
+    let Some(name) = name.filter(|_| !help) else {
+        return Ok(None);
+    };
+    if let Some(flag) = missing {
+        return Err(Failure::usage(format!("--{} 缺少参数值", flag)));
+    }
+
+    let allow = |known: &[&str]| -> Result<(), Failure> {
+        match options.iter().find(|(k, _)| !known.contains(&k.as_str())) {
+            Some((k, _)) => Err(Failure::usage(format!("{} 不支持选项 --{}", name, k))),
+            None => Ok(()),
+        }
+    };
+    let one_url = |positional: &[String]| -> Result<String, Failure> {
+        match positional {
+            [url] => Ok(url.clone()),
+            [] => Err(Failure::usage(format!("{} 需要分享链接", name))),
+            _ => Err(Failure::usage("只能指定一个分享链接")),
+        }
+    };
+    let last = |key: &str| {
+        options
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+    };
+
+    let command = match name.as_str() {
+        "login" => {
+            allow(&["cookie", "invert"])?;
+            Command::Login {
+                cookie: last("cookie"),
+                invert: last("invert").is_some(),
+            }
+        }
+        "ls" => {
+            allow(&[])?;
+            Command::Ls {
+                url: one_url(&positional)?,
+            }
+        }
+        "get" => {
+            allow(&["include", "out", "threads"])?;
+            let include = options
+                .iter()
+                .filter(|(k, _)| k == "include")
+                .map(|(_, v)| {
+                    glob::Pattern::new(v)
+                        .map_err(|e| Failure::usage(format!("无效的 --include {}: {}", v, e)))
+                })
+                .collect::<Result<_, _>>()?;
+            let threads = match last("threads") {
+                Some(v) => v
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| (1..=MAX_THREADS).contains(n))
+                    .ok_or_else(|| {
+                        Failure::usage(format!("--threads 应为 1-{} 的整数", MAX_THREADS))
+                    })?,
+                None => DEFAULT_THREADS,
+            };
+            Command::Get {
+                url: one_url(&positional)?,
+                include,
+                out: last("out"),
+                threads,
+            }
+        }
+        "whoami" | "logout" => {
+            allow(&[])?;
+            if !positional.is_empty() {
+                return Err(Failure::usage(format!("{} 不需要参数", name)));
+            }
+            if name == "whoami" {
+                Command::Whoami
+            } else {
+                Command::Logout
+            }
+        }
+        other => return Err(Failure::usage(format!("未知命令: {}", other))),
+    };
+    Ok(Some((command, verbose)))
+}
+
+/// 命令行工具的运行环境：数据目录、设置、登录会话与 HTTP 客户端
+struct Context {
+    data_dir: PathBuf,
+    settings: SettingsStore,
+    sessions: SessionStore,
+    clients: HttpClients,
+}
+
+impl Context {
+    fn open() -> Result<Self, Failure> {
+        let data_dir = std::env::var_os("QUARK_DL_DATA_DIR")
+            .map(PathBuf::from)
+            .or_else(|| dirs::data_dir().map(|d| d.join(APP_IDENTIFIER)))
+            .ok_or_else(|| {
+                Failure::from("无法确定数据目录，请设置 QUARK_DL_DATA_DIR".to_string())
+            })?;
+        std::fs::create_dir_all(&data_dir).map_err(|e| format!("创建数据目录失败: {}", e))?;
+        let settings = SettingsStore::open(data_dir.join("settings.json"));
+        let clients = HttpClients::new(&settings.get())?;
+        // 会话单独存放：桌面应用运行时会整体重写 sessions.json，共用一个文件会互相覆盖
+        let sessions = SessionStore::open(data_dir.join("cli-sessions.json"));
+        Ok(Self {
+            data_dir,
+            settings,
+            sessions,
+            clients,
+        })
+    }
+
+    fn session_file(&self) -> PathBuf {
+        self.data_dir.join("cli-session")
+    }
+
+    /// 当前会话 ID，未登录时为空
+    fn session_id(&self) -> String {
+        std::fs::read_to_string(self.session_file())
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default()
+    }
+
+    /// 当前会话的 Cookie；required 为 true 时未登录视为错误
+    fn cookie(&self, required: bool) -> Result<String, Failure> {
+        let cookie = self.sessions.cookie(&self.session_id())?;
+        if required && cookie.is_empty() {
+            return Err(Failure::new(EXIT_AUTH, "尚未登录，请先执行 quark-dl login"));
+        }
+        Ok(cookie)
+    }
+}
+
+async fn login(ctx: &Context, cookie: Option<String>, invert: bool) -> Result<(), Failure> {
+    let jar = match cookie {
+        Some(raw) => {
+            let raw = if raw == "-" {
+                let mut buf = String::new();
+                std::io::stdin()
+                    .read_to_string(&mut buf)
+                    .map_err(|e| format!("读取标准输入失败: {}", e))?;
+                buf
+            } else {
+                raw
+            };
+            let jar = CookieJar::parse(raw.trim().trim_start_matches("Cookie:"));
+            if jar.is_empty() {
+                return Err(Failure::usage("Cookie 为空"));
+            }
+            jar
+        }
+        None => qr_login(ctx, invert).await?,
+    };
+
+    let result =
+        quark_client::fetch_account_info_by_cookie(&ctx.clients.api(), &jar.header()).await?;
+    if !quark_client::can_read_account_info(result.status_code, &result.info) {
+        return Err(Failure::new(EXIT_AUTH, "Cookie 无效或已过期"));
+    }
+
+    let previous = ctx.session_id();
+    if !previous.is_empty() {
+        ctx.sessions.remove(&previous)?;
+    }
+    let id = ctx.sessions.create(jar)?;
+    std::fs::write(ctx.session_file(), &id).map_err(|e| format!("保存登录会话失败: {}", e))?;
+    println!("已登录: {}", result.info.nickname());
+    Ok(())
+}
+
+/// 在终端显示登录二维码并等待扫码确认
+async fn qr_login(ctx: &Context, invert: bool) -> Result<CookieJar, Failure> {
+    let api = ctx.clients.api();
+    let login = QrLogin::start(&api).await?;
+    println!(
+        "请使用夸克 APP 扫描二维码登录（{} 秒内有效）：\n",
+        QR_LIFETIME.as_secs()
+    );
+    println!("{}", login.render_terminal(invert)?);
+    println!("二维码显示不全时请放大终端窗口；扫不出时试试 --invert\n");
+    let jar = login
+        .wait(&api, |state| eprintln!("{}", state.describe()))
+        .await?;
+    Ok(jar)
+}
+
+async fn ls(ctx: &Context, url: &str) -> Result<(), Failure> {
+    let cookie = ctx.cookie(false)?;
+    let tree = quark_client::resolve_share(&ctx.clients.api(), &cookie, url).await?;
+    println!(
+        "{}（{} 个文件，{} 个目录）",
+        tree.title, tree.file_count, tree.dir_count
+    );
+    print_nodes(&tree.files);
+    Ok(())
+}
+
+fn print_nodes(nodes: &[ShareNode]) {
+    for node in nodes {
+        let indent = "  ".repeat(node.depth + 1);
+        if node.is_dir {
+            println!("{}{}/", indent, node.file_name);
+            print_nodes(&node.children);
+        } else {
+            println!(
+                "{}{}  {}",
+                indent,
+                node.file_name,
+                format_size(node.size as f64)
+            );
+        }
+    }
+}
+
+/// 展开目录树，收集匹配 include 的文件；include 为空表示全部
+fn collect_files<'a>(
+    nodes: &'a [ShareNode],
+    include: &[glob::Pattern],
+    out: &mut Vec<&'a ShareNode>,
+) {
+    let options = glob::MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+    for node in nodes {
+        if node.is_dir {
+            collect_files(&node.children, include, out);
+        } else if include.is_empty()
+            || include.iter().any(|p| {
+                p.matches_with(&node.path, options) || p.matches_with(&node.file_name, options)
+            })
+        {
+            out.push(node);
+        }
+    }
+}
+
+async fn get(
+    ctx: &Context,
+    url: &str,
+    include: &[glob::Pattern],
+    out: Option<&str>,
+    threads: usize,
+) -> Result<(), Failure> {
+    let cookie = ctx.cookie(true)?;
+    let api = ctx.clients.api();
+    let tree = quark_client::resolve_share(&api, &cookie, url).await?;
+    let mut files = Vec::new();
+    collect_files(&tree.files, include, &mut files);
+    if files.is_empty() {
+        return Err(Failure::from("没有匹配的文件".to_string()));
+    }
+    let total: u64 = files.iter().map(|f| f.size).sum();
+    eprintln!(
+        "{}: {} 个文件，共 {}",
+        tree.title,
+        files.len(),
+        format_size(total as f64)
+    );
+
+    let fids: Vec<String> = files.iter().map(|f| f.fid.clone()).collect();
+    let fid_tokens: Vec<String> = files.iter().map(|f| f.share_fid_token.clone()).collect();
+    let saved = quark_client::save_to_drive(
+        &api,
+        &cookie,
+        &tree.link.pwd_id,
+        &tree.stoken,
+        &fids,
+        &fid_tokens,
+    )
+    .await?;
+
+    let registry = Arc::new(DownloadRegistry::default());
+    let interrupted = Arc::new(AtomicBool::new(false));
+    {
+        let (registry, interrupted) = (Arc::clone(&registry), Arc::clone(&interrupted));
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                interrupted.store(true, Ordering::SeqCst);
+                registry.stop_all();
+            }
+        });
+    }
+
+    let root = ctx.settings.download_root(out);
+    let bandwidth = Arc::new(BandwidthLimit::new(ctx.settings.get().bandwidth));
+    tokio::spawn(Arc::clone(&bandwidth).run_schedule());
+    let download = Download {
+        ctx,
+        cookie: &cookie,
+        registry: &registry,
+        threads,
+        connections: Arc::new(ConcurrencyLimit::new(MAX_CONNECTIONS)),
+        bandwidth,
+    };
+    let (mut total, mut failed) = (0, 0);
+    // files 就是提交转存的顺序，save_to_drive 已确认转存结果与之一一对应
+    for (node, (_, saved_fid)) in files.iter().zip(saved.pairs()) {
+        if interrupted.load(Ordering::SeqCst) {
+            break;
+        }
+        total += 1;
+        // 直链有时效，轮到这个文件时再取，避免排在后面的文件拿到过期链接
+        let outcome = match quark_client::get_download_link(&api, &cookie, saved_fid).await {
+            Ok(item) => match downloader::share_target(
+                &root,
+                Some(&tree.title),
+                Some(&node.path),
+                &item.file_name,
+            ) {
+                Ok((dir, name)) => download.one(&dir, &name, &node.fid, &item).await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e.to_string()),
+        };
+        match outcome {
+            Ok(path) => eprintln!("完成: {}", path),
+            Err(e) => {
+                failed += 1;
+                eprintln!("失败: {}: {}", node.file_name, e);
+            }
+        }
+    }
+
+    if let Err(e) = saved.cleanup().await {
+        eprintln!("清理转存文件失败: {}", e);
+    }
+    get_outcome(total, failed, interrupted.load(Ordering::SeqCst))
+}
+
+/// 按下载结果选择退出码：中断 130，全部失败 1，部分失败 4
+fn get_outcome(total: usize, failed: usize, interrupted: bool) -> Result<(), Failure> {
+    if interrupted {
+        return Err(Failure::new(
+            EXIT_INTERRUPTED,
+            "已中断，部分数据已保留，重新执行同一命令可续传",
+        ));
+    }
+    match (total, failed) {
+        (0, _) => Err(Failure::from("没有获取到任何下载链接".to_string())),
+        (total, failed) if failed == total => {
+            Err(Failure::from(format!("{} 个文件全部下载失败", total)))
+        }
+        (total, failed) if failed > 0 => Err(Failure::new(
+            EXIT_PARTIAL,
+            format!("{} 个文件中有 {} 个下载失败", total, failed),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// 一次 get 中所有文件共用的下载参数
+struct Download<'a> {
+    ctx: &'a Context,
+    cookie: &'a str,
+    registry: &'a DownloadRegistry,
+    threads: usize,
+    connections: Arc<ConcurrencyLimit>,
+    bandwidth: Arc<BandwidthLimit>,
+}
+
+impl Download<'_> {
+    /// share_fid 是分享中的 fid：每次转存得到的网盘 fid 都不同，续传状态要按分享 fid 对应
+    async fn one(
+        &self,
+        target_dir: &Path,
+        save_name: &str,
+        share_fid: &str,
+        item: &DownloadItem,
+    ) -> Result<String, String> {
+        tokio::fs::create_dir_all(target_dir)
+            .await
+            .map_err(|e| format!("创建保存目录失败: {}", e))?;
+        let conflict = self.ctx.settings.conflict_policy(None);
+        let (save_path, state_path) =
+            downloader::prepare_target(target_dir, save_name, conflict).await;
+
+        let download_id = uuid::Uuid::new_v4().to_string()[..8].to_string();
+        let handle = self
+            .registry
+            .register(&download_id, &item.file_name, save_path.clone());
+        let dl = DownloadCtx {
+            progress: terminal_progress(),
+            client: self.ctx.clients.cdn(),
+            cookie: self.cookie.to_string(),
+            filename: item.file_name.clone(),
+            fid: share_fid.to_string(),
+            save_path,
+            state_path,
+            handle,
+            download_id: download_id.clone(),
+            thread_count: self.threads,
+            max_retries: downloader::DEFAULT_SEGMENT_RETRIES,
+            connections: Arc::clone(&self.connections),
+            throttle: self.bandwidth.throttle(),
+            conflict,
+            remote_md5: downloader::normalize_md5(&item.md5),
+        };
+        let result = downloader::do_download(dl, item.download_url.clone()).await;
+        self.registry.remove(&download_id);
+        // 结束进度行
+        if std::io::stderr().is_terminal() {
+            eprintln!();
+        }
+
+        Ok(result?
+            .get("path")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string())
+    }
+}
+
+/// 输出是终端时把进度画成一行，否则不画，避免日志里满是回车
+fn terminal_progress() -> Arc<dyn ProgressReporter> {
+    if std::io::stderr().is_terminal() {
+        Arc::new(TerminalProgress::default())
+    } else {
+        Arc::new(|_: DownloadProgress| {})
+    }
+}
+
+#[derive(Default)]
+struct TerminalProgress {
+    last_draw: Mutex<Option<Instant>>,
+}
+
+impl ProgressReporter for TerminalProgress {
+    fn report(&self, p: DownloadProgress) {
+        if p.status == ProgressStatus::Downloading {
+            let mut last = self.last_draw.lock().unwrap_or_else(|e| e.into_inner());
+            if last.is_some_and(|t| t.elapsed() < PROGRESS_INTERVAL) {
+                return;
+            }
+            *last = Some(Instant::now());
+        }
+        let percent = if p.total > 0 {
+            p.downloaded as f64 * 100.0 / p.total as f64
+        } else {
+            0.0
+        };
+        let detail = match p.status {
+            ProgressStatus::Downloading => format!("{}/s", format_size(p.speed)),
+            ProgressStatus::Paused => "已暂停".into(),
+            ProgressStatus::Verifying => "校验中".into(),
+            ProgressStatus::Done => "完成".into(),
+        };
+        let name: String = p.filename.chars().take(32).collect();
+        let line = format!(
+            "{} {:5.1}% {} / {}  {}",
+            name,
+            percent,
+            format_size(p.downloaded as f64),
+            format_size(p.total as f64),
+            detail
+        );
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "\r{:<78}", line);
+        let _ = stderr.flush();
+    }
+}
+
+async fn whoami(ctx: &Context) -> Result<(), Failure> {
+    let cookie = ctx.cookie(true)?;
+    let api = ctx.clients.api();
+    let result = quark_client::fetch_account_info_by_cookie(&api, &cookie).await?;
+    if !quark_client::can_read_account_info(result.status_code, &result.info) {
+        return Err(Failure::new(EXIT_AUTH, "登录已失效，请重新登录"));
+    }
+    println!("账号: {}", result.info.nickname());
+    let member = quark_client::get_member(&api, &cookie).await?;
+    if let Some(kind) = member.member_type.as_deref() {
+        println!("会员: {}", kind);
+    }
+    println!(
+        "容量: {} / {}",
+        format_size(member.use_capacity as f64),
+        format_size(member.total_capacity as f64)
+    );
+    Ok(())
+}
+
+async fn logout(ctx: &Context) -> Result<(), Failure> {
+    let id = ctx.session_id();
+    if id.is_empty() {
+        println!("当前未登录");
+        return Ok(());
+    }
+    // 会话已丢失时只清理本地记录
+    if let Ok(cookie) = ctx.cookie(true) {
+        if let Err(e) = quark_client::logout(&ctx.clients.api(), &cookie).await {
+            eprintln!("服务端退出失败（本地登录信息仍会清除）: {}", e);
+        }
+    }
+    ctx.sessions.remove(&id)?;
+    let _ = std::fs::remove_file(ctx.session_file());
+    println!("已退出登录");
+    Ok(())
+}
+
+fn format_size(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", value as u64, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+async fn run(command: Command) -> Result<(), Failure> {
+    let ctx = Context::open()?;
+    match command {
+        Command::Login { cookie, invert } => login(&ctx, cookie, invert).await,
+        Command::Ls { url } => ls(&ctx, &url).await,
+        Command::Get {
+            url,
+            include,
+            out,
+            threads,
+        } => get(&ctx, &url, &include, out.as_deref(), threads).await,
+        Command::Whoami => whoami(&ctx).await,
+        Command::Logout => logout(&ctx).await,
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let (command, verbose) = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(Some(parsed)) => parsed,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("quark-dl: {}\n运行 quark-dl --help 查看用法", e.message);
+            return ExitCode::from(e.code);
+        }
+    };
+    quark_core::set_verbose(verbose);
+
+    match run(command).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("quark-dl: {}", e.message);
+            ExitCode::from(e.code)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(list: &[&str]) -> Result<Option<(Command, bool)>, Failure> {
+        parse_args(list.iter().map(|s| s.to_string()).collect())
+    }
+
+    fn usage_error(list: &[&str]) -> bool {
+        matches!(
+            parse(list),
+            Err(Failure {
+                code: EXIT_USAGE,
+                ..
+            })
+        )
+    }
+
+    #[test]
+    fn parses_get_options() {
+        let Some((
+            Command::Get {
+                url,
+                include,
+                out,
+                threads,
+            },
+            verbose,
+        )) = parse(&[
+            "get",
+            "https://pan.quark.cn/s/abc123",
+            "--include",
+            "*.mkv",
+            "--include=season1/*",
+            "--out",
+            "/data",
+            "--threads=8",
+        ])
+        .unwrap()
+        else {
+            panic!("应解析为 get");
+        };
+        assert_eq!(url, "https://pan.quark.cn/s/abc123");
+        assert_eq!(include.len(), 2);
+        assert_eq!(out.as_deref(), Some("/data"));
+        assert_eq!(threads, 8);
+        assert!(!verbose);
+    }
+
+    #[test]
+    fn threads_must_be_in_range() {
+        let threads = |value: &str| match parse(&["get", "url", "--threads", value]) {
+            Ok(Some((Command::Get { threads, .. }, _))) => Some(threads),
+            _ => None,
+        };
+        assert_eq!(threads("1"), Some(1));
+        assert_eq!(threads("256"), Some(256));
+        assert_eq!(threads("0"), None);
+        assert_eq!(threads("257"), None);
+        assert_eq!(threads("many"), None);
+        assert!(matches!(
+            parse(&["get", "url"]),
+            Ok(Some((
+                Command::Get {
+                    threads: DEFAULT_THREADS,
+                    ..
+                },
+                _
+            )))
+        ));
+    }
+
+    #[test]
+    fn verbose_only_counts_in_flag_position() {
+        let Some((Command::Get { out, .. }, verbose)) =
+            parse(&["get", "url", "--out", "-v"]).unwrap()
+        else {
+            panic!("应解析为 get");
+        };
+        assert_eq!(out.as_deref(), Some("-v"));
+        assert!(!verbose);
+        assert!(matches!(
+            parse(&["-v", "whoami"]),
+            Ok(Some((Command::Whoami, true)))
+        ));
+        assert!(matches!(
+            parse(&["ls", "url", "--verbose"]),
+            Ok(Some((Command::Ls { .. }, true)))
+        ));
+    }
+
+    #[test]
+    fn help_and_usage_errors() {
+        assert!(matches!(parse(&[]), Ok(None)));
+        assert!(matches!(parse(&["get", "--help"]), Ok(None)));
+        assert!(matches!(parse(&["get", "url", "--out", "-h"]), Ok(Some(_))));
+        assert!(usage_error(&["download"]));
+        assert!(usage_error(&["get"]));
+        assert!(usage_error(&["get", "url", "url2"]));
+        assert!(usage_error(&["get", "url", "--out"]));
+        assert!(usage_error(&["ls", "url", "--threads", "4"]));
+        assert!(usage_error(&["get", "url", "--include", "[a"]));
+        assert!(usage_error(&["whoami", "extra"]));
+        assert!(matches!(
+            parse(&["login", "--invert"]),
+            Ok(Some((
+                Command::Login {
+                    cookie: None,
+                    invert: true
+                },
+                false
+            )))
+        ));
+    }
+
+    fn node(path: &str, children: Vec<ShareNode>) -> ShareNode {
+        ShareNode {
+            fid: path.into(),
+            file_name: path.rsplit('/').next().unwrap_or(path).into(),
+            size: 1,
+            format_type: String::new(),
+            updated_at: 0,
+            share_fid_token: String::new(),
+            is_dir: !children.is_empty(),
+            depth: path.matches('/').count(),
+            path: path.into(),
+            children,
+        }
+    }
+
+    #[test]
+    fn include_matches_path_or_file_name() {
+        let tree = vec![
+            node(
+                "season1",
+                vec![
+                    node("season1/e01.mkv", vec![]),
+                    node("season1/e01.srt", vec![]),
+                ],
+            ),
+            node("readme.txt", vec![]),
+        ];
+        let matched = |patterns: &[&str]| {
+            let include: Vec<glob::Pattern> = patterns
+                .iter()
+                .map(|p| glob::Pattern::new(p).unwrap())
+                .collect();
+            let mut out = Vec::new();
+            collect_files(&tree, &include, &mut out);
+            out.iter().map(|n| n.path.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(matched(&[]).len(), 3);
+        assert_eq!(matched(&["*.mkv"]), ["season1/e01.mkv"]);
+        assert_eq!(
+            matched(&["season1/*"]),
+            ["season1/e01.mkv", "season1/e01.srt"]
+        );
+        // * 不跨目录
+        assert!(matched(&["*/readme.txt"]).is_empty());
+        assert_eq!(
+            matched(&["*.srt", "*.txt"]),
+            ["season1/e01.srt", "readme.txt"]
+        );
+    }
+
+    #[test]
+    fn exit_codes() {
+        let code = |result: Result<(), Failure>| result.err().map_or(0, |f| f.code);
+        assert_eq!(code(get_outcome(3, 0, false)), 0);
+        assert_eq!(code(get_outcome(3, 3, false)), EXIT_ERROR);
+        assert_eq!(code(get_outcome(3, 1, false)), EXIT_PARTIAL);
+        assert_eq!(code(get_outcome(3, 0, true)), EXIT_INTERRUPTED);
+        assert_eq!(
+            Failure::from(QuarkError::AuthExpired(String::new())).code,
+            EXIT_AUTH
+        );
+        assert_eq!(
+            Failure::from(QuarkError::Timeout(String::new())).code,
+            EXIT_ERROR
+        );
+        assert_eq!(
+            [
+                EXIT_ERROR,
+                EXIT_USAGE,
+                EXIT_AUTH,
+                EXIT_PARTIAL,
+                EXIT_INTERRUPTED
+            ],
+            [1, 2, 3, 4, 130]
+        );
+    }
+}
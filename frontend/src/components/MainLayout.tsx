@@ -5,7 +5,8 @@ import { useQuarkStore } from '../store/useQuarkStore';
 import { quarkApi } from '../services/quarkApi';
 import { AuthModal } from '../features/auth/AuthModal';
 import { motion, AnimatePresence } from 'framer-motion';
-import { Zap, CheckCircle2, AlertCircle, Info, AlertTriangle } from 'lucide-react';
+import { invoke } from '@tauri-apps/api/core';
+import { Zap, CheckCircle2, AlertCircle, Info, AlertTriangle, Pause, Play, X } from 'lucide-react';
 import { cn, formatSize, getErrorMessage } from '../utils';
 const ToastIcon: React.FC<{ type: string }> = ({ type }) => {
     switch (type) {
@@ -34,8 +35,8 @@ export const MainLayout: React.FC<MainLayoutProps> = ({
 }) => {
     const {
         isLoggedIn,
-        cookie,
-        setCookie,
+        session,
+        setSession,
         addLog,
         setCapacity,
         setCapacityLoading,
@@ -44,8 +45,25 @@ export const MainLayout: React.FC<MainLayoutProps> = ({
         toast,
         notify,
         setLoggingOut,
+        updateDownload,
+        removeDownload,
     } = useQuarkStore();
 
+    // 单个下载的暂停 / 恢复 / 取消，互不影响
+    const controlDownload = async (id: string, filename: string, action: 'pause' | 'resume' | 'cancel') => {
+        try {
+            await invoke(`${action}_download`, { id });
+            if (action === 'cancel') {
+                removeDownload(id);
+                addLog(`已取消下载: ${filename}`, 'info');
+            } else {
+                updateDownload(id, { filename, speed: 0, status: action === 'pause' ? 'paused' : 'downloading' });
+            }
+        } catch (error: unknown) {
+            notify(`操作失败: ${getErrorMessage(error)}`, 'error');
+        }
+    };
+
     // 将活跃下载转为数组，计算百分比
     const dlEntries = useMemo(() => {
         if (activeDownloads.size === 0) return [];
@@ -88,8 +106,8 @@ export const MainLayout: React.FC<MainLayoutProps> = ({
 
     const handleLogout = async () => {
         setLoggingOut(true);
-        if (!cookie.trim()) {
-            setCookie('');
+        if (!session) {
+            setSession('');
             addLog('本地登录状态已清理', 'info');
             notify('已退出登录', 'info');
             setLoggingOut(false);
@@ -118,7 +136,7 @@ export const MainLayout: React.FC<MainLayoutProps> = ({
             logoutMessage = `远端退出状态校验失败，已清理本地登录状态（${getErrorMessage(error)}）`;
         }
 
-        setCookie('');
+        setSession('');
         setCapacity(0, 0);
         addLog('已退出登录', 'info');
         notify(logoutMessage, logoutType);
@@ -161,20 +179,38 @@ export const MainLayout: React.FC<MainLayoutProps> = ({
                                         <div key={dl.id}>
                                             <div className="flex justify-between items-center mb-1.5">
                                                 <span className="text-xs font-medium text-slate-300 truncate max-w-[320px]" title={dl.filename}>
-                                                    {dl.status === 'merging'
-                                                        ? `${dl.filename.slice(0, 28)} 合并中...`
-                                                        : dl.filename.length > 28 ? dl.filename.slice(0, 28) + '...' : dl.filename}
+                                                    {dl.status === 'paused'
+                                                        ? `${dl.filename.slice(0, 28)} 已暂停`
+                                                        : dl.status === 'verifying'
+                                                            ? `${dl.filename.slice(0, 28)} 校验中`
+                                                            : dl.filename.length > 28 ? dl.filename.slice(0, 28) + '...' : dl.filename}
                                                 </span>
                                                 <div className="flex items-center gap-2 shrink-0 ml-2">
-                                                    {dl.status !== 'merging' && (
-                                                        <span className="text-[10px] text-slate-400 tabular-nums">
-                                                            {formatSize(dl.downloaded)}/{formatSize(dl.total)}
-                                                            {dl.speed > 0 && ` ${(dl.speed / 1024 / 1024).toFixed(1)} MB/s`}
-                                                        </span>
-                                                    )}
+                                                    <span className="text-[10px] text-slate-400 tabular-nums">
+                                                        {formatSize(dl.downloaded)}/{formatSize(dl.total)}
+                                                        {dl.status === 'downloading' && dl.speed > 0 && ` ${(dl.speed / 1024 / 1024).toFixed(1)} MB/s`}
+                                                    </span>
                                                     <span className="text-xs font-semibold text-white tabular-nums w-8 text-right">
                                                         {dl.percent}%
                                                     </span>
+                                                    {dl.status !== 'done' && (
+                                                        <>
+                                                            <button
+                                                                className="text-slate-400 hover:text-white transition-colors"
+                                                                title={dl.status === 'paused' ? '继续' : '暂停'}
+                                                                onClick={() => controlDownload(dl.id, dl.filename, dl.status === 'paused' ? 'resume' : 'pause')}
+                                                            >
+                                                                {dl.status === 'paused' ? <Play size={12} /> : <Pause size={12} />}
+                                                            </button>
+                                                            <button
+                                                                className="text-slate-400 hover:text-rose-400 transition-colors"
+                                                                title="取消"
+                                                                onClick={() => controlDownload(dl.id, dl.filename, 'cancel')}
+                                                            >
+                                                                <X size={12} />
+                                                            </button>
+                                                        </>
+                                                    )}
                                                 </div>
                                             </div>
                                             <div className="h-1.5 w-full bg-white/10 rounded-full overflow-hidden">
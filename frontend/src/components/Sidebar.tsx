@@ -14,6 +14,7 @@ import { cn } from '../utils';
 import { useQuarkStore } from '../store/useQuarkStore';
 import { Button } from './ui/button';
 import { CapacityStats } from '../features/capacity/CapacityStats';
+import { DownloadSettingsPanel } from '../features/settings/DownloadSettingsPanel';
 
 interface SidebarProps {
     onLogout: () => void;
@@ -117,6 +118,16 @@ export const Sidebar: React.FC<SidebarProps> = ({
                                 className="w-full h-1 bg-slate-200 rounded-lg appearance-none cursor-pointer accent-indigo-500"
                             />
                         </div>
+                        <label className="p-4 flex items-center justify-between bg-white/10 rounded-2xl border border-white/10 cursor-pointer">
+                            <span className="text-[10px] font-bold text-slate-400 uppercase">按分享标题建目录</span>
+                            <input
+                                type="checkbox"
+                                checked={useQuarkStore.getState().prefixShareTitle}
+                                onChange={(e) => useQuarkStore.getState().setPrefixShareTitle(e.target.checked)}
+                                className="accent-indigo-500"
+                            />
+                        </label>
+                        <DownloadSettingsPanel />
                     </div>
                 )}
 
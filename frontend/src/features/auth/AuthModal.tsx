@@ -17,7 +17,7 @@ interface AuthModalProps {
 
 export const AuthModal: React.FC<AuthModalProps> = ({ isOpen, onClose, onLogout }) => {
     const [activeTab, setActiveTab] = useState<'qr' | 'cookie'>('qr');
-    const { isLoggedIn, setCookie, addLog, notify } = useQuarkStore();
+    const { isLoggedIn, setSession, addLog, notify } = useQuarkStore();
 
     // QR Login State
     const [qrToken, setQrToken] = useState('');
@@ -70,8 +70,8 @@ export const AuthModal: React.FC<AuthModalProps> = ({ isOpen, onClose, onLogout
     const completeQrLogin = useCallback(async (st: string) => {
         try {
             const res = await quarkApi.getQrCookie(st);
-            if (res.cookie) {
-                setCookie(res.cookie);
+            if (res.session) {
+                setSession(res.session);
                 addLog('登录成功', 'success');
                 notify('扫码登录成功，欢迎回来', 'success');
                 setTimeout(handleClose, 1500);
@@ -84,7 +84,7 @@ export const AuthModal: React.FC<AuthModalProps> = ({ isOpen, onClose, onLogout
             setQrHint(`获取 Cookie 失败: ${message}`);
             notify(`登录失败: ${message}`, 'error');
         }
-    }, [addLog, handleClose, setCookie, notify]);
+    }, [addLog, handleClose, setSession, notify]);
 
     useEffect(() => {
         let pollTimer: number;
@@ -112,19 +112,26 @@ export const AuthModal: React.FC<AuthModalProps> = ({ isOpen, onClose, onLogout
         const trimmed = cookieInput.trim();
         if (!trimmed) return;
         setIsSaving(true);
+        // 验证失败时丢弃刚建立的会话
+        const discard = async () => {
+            await quarkApi.deleteSession().catch(() => undefined);
+            setSession('');
+        };
         try {
-            setCookie(trimmed);
+            const { session } = await quarkApi.createSession(trimmed);
+            setSession(session);
             const res = await quarkApi.getMemberInfo();
             if (res.status === 200 || res.code === 0) {
+                setCookieInput('');
                 addLog('Cookie 已保存并验证通过', 'success');
                 notify('登录成功，欢迎回来', 'success');
                 handleClose();
             } else {
-                setCookie('');
+                await discard();
                 notify('Cookie 无效，请检查后重新输入', 'error');
             }
         } catch {
-            setCookie('');
+            await discard();
             notify('Cookie 验证失败，请检查是否正确', 'error');
         } finally {
             setIsSaving(false);
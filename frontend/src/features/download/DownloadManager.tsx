@@ -3,11 +3,9 @@ import { CheckSquare, Square, Loader2, Zap } from 'lucide-react';
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
 import { useQuarkStore } from '../../store/useQuarkStore';
-import { quarkApi } from '../../services/quarkApi';
-import { sleep } from '../../utils';
 import { formatSize, cn, getErrorMessage } from '../../utils';
 import { Button } from '../../components/ui/button';
-import type { ShareFileNode } from '../../types/quark';
+import type { QueueJob, ShareFileNode } from '../../types/quark';
 
 interface DownloadProgressEvent {
     id: string;
@@ -16,8 +14,13 @@ interface DownloadProgressEvent {
     total: number;
     speed?: number;
     status?: string;
+    retries?: number;
+    segments?: { index: number; retries: number; last_error?: string | null }[];
 }
 
+const isFinished = (job: QueueJob) =>
+    job.state === 'done' || job.state === 'failed' || job.state === 'cancelled';
+
 export const DownloadManager: React.FC = () => {
     const {
         shareInfo,
@@ -29,12 +32,16 @@ export const DownloadManager: React.FC = () => {
         setProgress,
         updateDownload,
         removeDownload,
+        jobs,
+        upsertJob,
+        setJobs,
         addLog,
         clearLogs,
-        cookie,
+        session,
         isLoggedIn,
         downloadConcurrency,
         downloadThreads,
+        prefixShareTitle,
         notify
     } = useQuarkStore();
 
@@ -55,7 +62,7 @@ export const DownloadManager: React.FC = () => {
                 downloaded,
                 total,
                 speed: speed ?? 0,
-                status: status === 'merging' ? 'merging' : 'downloading',
+                status: status === 'paused' || status === 'verifying' ? status : 'downloading',
             });
         });
 
@@ -78,22 +85,61 @@ export const DownloadManager: React.FC = () => {
             .reduce((acc: number, f: ShareFileNode) => acc + f.size, 0);
     };
 
-    const triggerDownload = async (url: string, filename: string) => {
-        try {
-            const result = await invoke('download_file', {
-                url,
-                cookie,
-                filename,
-                threadCount: downloadThreads,
-            });
-            const res = result as { path?: string; size?: number };
-            addLog(`  已保存到: ${res.path ?? '未知路径'}`, 'success');
-        } catch (error: unknown) {
-            const message = getErrorMessage(error);
-            addLog(`下载失败: ${message}`, 'error');
-            notify(`下载失败: ${message}`, 'error');
+    // 下载队列在后端运行：页面刷新后从 list_jobs 恢复未完成的任务，之后靠 job-update 事件同步
+    useEffect(() => {
+        invoke<QueueJob[]>('list_jobs')
+            .then((list) => setJobs(list.filter((job) => !isFinished(job))))
+            .catch(() => {});
+
+        const unlisten = listen<QueueJob>('job-update', (event) => {
+            const job = event.payload;
+            upsertJob(job);
+            if (job.state === 'resolving') {
+                addLog(`正在转存并获取下载链接: ${job.filename}`);
+            } else if (job.state === 'done') {
+                addLog(`  已保存到: ${job.path ?? '未知路径'}`, 'success');
+            } else if (job.state === 'failed') {
+                removeDownload(job.id);
+                addLog(`下载失败: ${job.filename} (${job.error ?? '未知错误'})`, 'error');
+            } else if (job.state === 'cancelled') {
+                // 任务也可能经由 HTTP 接口（/api/jobs）在别处被取消
+                removeDownload(job.id);
+                addLog(`已取消: ${job.filename}`, 'warn');
+            }
+        });
+
+        return () => {
+            unlisten.then(fn => fn());
+        };
+    }, [upsertJob, setJobs, removeDownload, addLog]);
+
+    // 按队列任务汇总批量进度，全部结束后提示并清空
+    useEffect(() => {
+        const list = Array.from(jobs.values());
+        if (list.length === 0) return;
+
+        const finished = list.filter(isFinished).length;
+        if (finished < list.length) {
+            setDownloading(true);
+            setProgress(finished, list.length, `正在处理 ${finished}/${list.length}`);
+            return;
         }
-    };
+
+        const failed = list.filter((job) => job.state === 'failed').length;
+        const summary = failed > 0
+            ? `全部完成，共 ${list.length} 个文件，失败 ${failed} 个`
+            : `全部完成，共处理 ${list.length} 个文件`;
+        setDownloading(false);
+        setProgress(list.length, list.length, '全部完成');
+        addLog(summary, failed > 0 ? 'warn' : 'success');
+        notify(summary, failed > 0 ? 'warn' : 'success');
+
+        const timer = setTimeout(() => {
+            setJobs([]);
+            setProgress(0, 0, '');
+        }, 3000);
+        return () => clearTimeout(timer);
+    }, [jobs, setJobs, setDownloading, setProgress, addLog, notify]);
 
     const startDownload = async () => {
         if (selectedFids.size === 0 || downloading) return;
@@ -104,125 +150,37 @@ export const DownloadManager: React.FC = () => {
         }
 
         const filesToDownload = shareInfo.allFiles.filter((f: ShareFileNode) => selectedFids.has(f.fid));
-        setDownloading(true);
-        clearLogs();
+        if (filesToDownload.length === 0) {
+            addLog('没有可处理的文件', 'warn');
+            notify('没有可处理的文件', 'warn');
+            return;
+        }
 
+        clearLogs();
         try {
-            const total = filesToDownload.length;
-            let done = 0;
-            let nextIndex = 0;
-            const concurrency = Math.min(downloadConcurrency, total);
-
-            if (total === 0) {
-                addLog('没有可处理的文件', 'warn');
-                notify('没有可处理的文件', 'warn');
-                return;
-            }
-
-            setProgress(0, total, `准备中...`);
-            addLog(`开始批量处理，共 ${total} 个文件，并发 ${concurrency}`);
-
-            const processFile = async (index: number) => {
-                const f = filesToDownload[index];
-                addLog(`[${index + 1}/${total}] 正在处理: ${f.file_name}`);
-
-                let savedFid: string | null = null;
-                try {
-                    addLog('  正在转存到网盘...');
-                    const sr = await quarkApi.saveFiles(
-                        shareInfo.pwdId,
-                        shareInfo.stoken,
-                        [f.fid],
-                        [f.share_fid_token]
-                    );
-
-                    if (sr.status !== 200 || !sr.data) {
-                        throw new Error(sr.message || '转存失败');
-                    }
-
-                    const taskId = sr.data.task_id;
-
-                    for (let r = 0; r < 20; r++) {
-                        await sleep(500);
-                        const tr = await quarkApi.queryTask(taskId, r);
-                        if (tr.data && tr.data.status === 2) {
-                            savedFid = tr.data.save_as?.save_as_top_fids?.[0] ?? null;
-                            break;
-                        }
-                    }
-
-                    if (!savedFid) {
-                        addLog('  转存超时或未获取到文件 ID，跳过', 'warn');
-                        return;
-                    }
-
-                    addLog('  获取下载链接...');
-                    const dl = await quarkApi.getDownloadUrl([savedFid]);
-                    if (dl.status === 200 && dl.data?.[0]?.download_url) {
-                        // 拿到 CDN URL 后立即删除转存文件（URL 自带鉴权 token，删除后仍有效）
-                        try {
-                            await quarkApi.deleteFiles([savedFid]);
-                            addLog('  转存文件已清理');
-                        } catch (e: unknown) {
-                            addLog(`  转存清理失败: ${getErrorMessage(e)}`, 'warn');
-                        }
-                        savedFid = null; // 已删除，防止 finally 重复处理
-
-                        await triggerDownload(dl.data[0].download_url, f.file_name);
-                    } else {
-                        addLog(`  获取链接失败: ${dl.message || '未知错误'}`, 'error');
-                    }
-                } catch (error: unknown) {
-                    const message = getErrorMessage(error);
-                    addLog(`  处理出错: ${message}`, 'error');
-                } finally {
-                    // 如果在获取下载链接之前就失败了，savedFid 仍不为 null，需要兜底清理
-                    if (savedFid) {
-                        try {
-                            await quarkApi.deleteFiles([savedFid]);
-                            addLog('  转存文件已兜底清理');
-                        } catch (e: unknown) {
-                            addLog(`  兜底清理失败: ${getErrorMessage(e)}`, 'warn');
-                        }
-                    }
-
-                    done += 1;
-                    if (done >= total) {
-                        setProgress(done, total, '全部完成');
-                        setTimeout(() => {
-                            setProgress(0, 0, '');
-                        }, 3000);
-                    } else {
-                        const progressText = `正在处理 ${done}/${total}`;
-                        setProgress(done, total, progressText);
-                    }
-                }
-            };
-
-            const worker = async () => {
-                while (true) {
-                    const index = nextIndex;
-                    nextIndex += 1;
-                    if (index >= total) return;
-
-                    await processFile(index);
-
-                    if (nextIndex < total) {
-                        await sleep(300);
-                    }
-                }
-            };
-
-            await Promise.all(Array.from({ length: concurrency }, () => worker()));
-            setProgress(total, total, '全部完成');
-            addLog(`全部完成，共处理 ${total} 个文件`, 'success');
-            notify(`全部完成，共处理 ${total} 个文件`, 'success');
+            await invoke('set_queue_limits', { fileConcurrency: downloadConcurrency });
+            const created = await invoke<QueueJob[]>('enqueue_share_files', {
+                request: {
+                    session,
+                    pwd_id: shareInfo.pwdId,
+                    stoken: shareInfo.stoken,
+                    files: filesToDownload.map((f: ShareFileNode) => ({
+                        fid: f.fid,
+                        share_fid_token: f.share_fid_token,
+                        file_name: f.file_name,
+                        size: f.size,
+                        path: f.path,
+                    })),
+                    share_title: prefixShareTitle ? shareInfo.title : undefined,
+                    thread_count: downloadThreads,
+                },
+            });
+            created.forEach(upsertJob);
+            addLog(`已加入下载队列，共 ${created.length} 个文件，并发 ${downloadConcurrency}`);
         } catch (error: unknown) {
             const message = getErrorMessage(error);
-            addLog(`流程出错: ${message}`, 'error');
-            notify(`流程出错: ${message}`, 'error');
-        } finally {
-            setDownloading(false);
+            addLog(`加入下载队列失败: ${message}`, 'error');
+            notify(`加入下载队列失败: ${message}`, 'error');
         }
     };
 
@@ -1,8 +1,7 @@
 import React, { useState, useEffect, useRef } from 'react';
 import { Loader2, Link2, ChevronUp, Sparkles, Zap } from 'lucide-react';
 import { useQuarkStore } from '../../store/useQuarkStore';
-import { quarkApi, parseShareUrl, collectFiles } from '../../services/quarkApi';
-import type { RawFileItem, ShareFileNode } from '../../types/quark';
+import { quarkApi, collectFiles, toShareFileNodes } from '../../services/quarkApi';
 import { getErrorMessage, cn } from '../../utils';
 import { Button } from '../../components/ui/button';
 import { Input } from '../../components/ui/input';
@@ -10,14 +9,10 @@ import { SearchContainer, GradientGlow } from '../../components/ui/card';
 import { DownloadManager } from '../download/DownloadManager';
 import { motion, AnimatePresence } from 'framer-motion';
 
-type ParseProgressReporter = (list: RawFileItem[], depth: number, page: number) => void;
-
-const isDirectory = (item: RawFileItem) => item.dir || item.file_type === 0 || item.obj_category === 'dir';
-
 export const ShareParser: React.FC = () => {
     const [url, setUrl] = useState('');
     const {
-        cookie,
+        isLoggedIn,
         setShareInfo,
         resetShareInfo,
         addLog,
@@ -38,79 +33,9 @@ export const ShareParser: React.FC = () => {
 
     const prevSelectedSize = useRef(selectedFids.size);
 
-    const getFileListPaged = async (
-        pid: string,
-        st: string,
-        fid: string = '0',
-        depth: number = 0,
-        onPageLoaded?: ParseProgressReporter
-    ) => {
-        let all: RawFileItem[] = [];
-        let page = 1;
-        let total = Infinity;
-
-        while (all.length < total) {
-            const res = await quarkApi.getShareDetail(pid, st, fid, page);
-            if (res.status !== 200 || !res.data) {
-                throw new Error(res.message || '获取文件列表失败');
-            }
-
-            const list = (res.data.list || []) as RawFileItem[];
-            if (res.metadata?._total !== undefined) {
-                total = res.metadata._total;
-            } else if (list.length === 0) {
-                break;
-            }
-
-            all = [...all, ...list];
-            onPageLoaded?.(list, depth, page);
-
-            if (list.length < 50) break;
-            page++;
-        }
-        return all;
-    };
-
-    const getFileTreeRecursive = async (
-        pid: string,
-        st: string,
-        fid: string = '0',
-        depth: number = 0,
-        parentPath: string = '',
-        onPageLoaded?: ParseProgressReporter
-    ): Promise<ShareFileNode[]> => {
-        const list = await getFileListPaged(pid, st, fid, depth, onPageLoaded);
-        const nodes: ShareFileNode[] = [];
-
-        for (const item of list) {
-            const isDir = isDirectory(item);
-            const path = parentPath ? `${parentPath}/${item.file_name}` : item.file_name;
-
-            const node: ShareFileNode = {
-                fid: item.fid,
-                file_name: item.file_name,
-                size: item.size || 0,
-                format_type: item.format_type || '',
-                updated_at: item.updated_at || item.l_updated_at || 0,
-                share_fid_token: item.share_fid_token || '',
-                isDir,
-                depth,
-                path,
-                children: [],
-                expanded: depth === 0,
-            };
-
-            if (isDir) {
-                node.children = await getFileTreeRecursive(pid, st, item.fid, depth + 1, path, onPageLoaded);
-            }
-            nodes.push(node);
-        }
-        return nodes;
-    };
-
     const handleParse = async () => {
         if (!url.trim()) return;
-        if (!cookie) {
+        if (!isLoggedIn) {
             const message = '请先登录账号';
             setShareInfo({ status: message, statusType: 'warn' });
             notify(message, 'warn');
@@ -121,54 +46,18 @@ export const ShareParser: React.FC = () => {
         setShowSuccessPulse(false);
         addLog(`开始解析: ${url.substring(0, 50)}...`);
 
-        const parseStats = {
-            scannedItems: 0,
-            scannedFiles: 0,
-            scannedDirs: 0,
-            lastToastAt: 0,
-        };
-
-        const reportParseProgress: ParseProgressReporter = (list, depth, page) => {
-            let fileCount = 0;
-            let dirCount = 0;
-
-            for (const item of list) {
-                if (isDirectory(item)) dirCount += 1;
-                else fileCount += 1;
-            }
-
-            parseStats.scannedItems += list.length;
-            parseStats.scannedFiles += fileCount;
-            parseStats.scannedDirs += dirCount;
-
-            const message = `解析中：已扫描 ${parseStats.scannedItems} 项（文件 ${parseStats.scannedFiles}，目录 ${parseStats.scannedDirs}）`;
-            setShareInfo({ status: message, statusType: 'info' });
-
-            const now = Date.now();
-            const shouldNotify = now - parseStats.lastToastAt > 1000 || depth === 0 || page === 1;
-            if (shouldNotify) {
-                notify(message, 'info');
-                parseStats.lastToastAt = now;
-            }
-        };
-
         try {
-            const { pwdId, passcode, pdirFid } = parseShareUrl(url);
-            if (!pwdId) throw new Error('无法从链接中提取分享 ID');
-
-            setShareInfo({ status: '正在获取访问令牌...', statusType: 'info' });
-            notify('正在获取访问令牌...', 'info');
-            const resToken = await quarkApi.getShareToken(pwdId, passcode);
-            if (!resToken.data?.stoken) throw new Error(resToken.message || '获取访问令牌失败');
-
-            const stoken = resToken.data.stoken;
+            // 令牌获取与目录遍历都在后端完成（分页和子目录并发拉取）
+            setShareInfo({ status: '正在解析目录结构...', statusType: 'info' });
             notify('正在解析目录结构...', 'info');
-            const files = await getFileTreeRecursive(pwdId, stoken, pdirFid, 0, '', reportParseProgress);
+            const tree = await quarkApi.resolveShare(url);
+            const files = toShareFileNodes(tree.files);
             const allFiles = collectFiles(files);
 
             setShareInfo({
-                pwdId,
-                stoken,
+                pwdId: tree.pwd_id,
+                stoken: tree.stoken,
+                title: tree.title,
                 shareUrl: url,
                 files,
                 allFiles,
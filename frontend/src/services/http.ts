@@ -1,33 +1,29 @@
 import axios from 'axios';
+import { invoke } from '@tauri-apps/api/core';
 import { useQuarkStore } from '../store/useQuarkStore';
 
 const http = axios.create({
     baseURL: import.meta.env.VITE_API_BASE_URL || 'http://127.0.0.1:3000/api',
 });
 
-// 请求拦截：自动注入 x-cookie
-http.interceptors.request.use((config) => {
-    const cookie = localStorage.getItem('quark_cookie');
-    if (cookie) {
-        config.headers['x-cookie'] = cookie;
-    }
-    return config;
-});
+// 内嵌服务每次启动生成新的访问令牌，首次请求时向后端取一次
+let apiToken: Promise<string> | null = null;
+const getApiToken = () => {
+    apiToken ??= invoke<string>('get_api_token').catch((error: unknown) => {
+        apiToken = null;
+        throw error;
+    });
+    return apiToken;
+};
 
-// 响应拦截：处理 x-append-cookie
-http.interceptors.response.use((response) => {
-    const appendCookie = response.headers['x-append-cookie'];
-    if (appendCookie) {
-        const currentCookie = localStorage.getItem('quark_cookie') || '';
-        if (!currentCookie.includes('__puus=')) {
-            const newCookie = currentCookie ? `${currentCookie}; ${appendCookie}` : appendCookie;
-            localStorage.setItem('quark_cookie', newCookie);
-            // 同步更新 Zustand store，确保下载等功能能拿到完整 Cookie
-            useQuarkStore.getState().setCookie(newCookie);
-            console.log('[HTTP] Automatically appended __puus cookie');
-        }
+// 请求拦截：自动注入访问令牌与 x-session（Cookie 由后端按会话保存与续期）
+http.interceptors.request.use(async (config) => {
+    config.headers.Authorization = `Bearer ${await getApiToken()}`;
+    const { session } = useQuarkStore.getState();
+    if (session) {
+        config.headers['x-session'] = session;
     }
-    return response;
+    return config;
 });
 
 export default http;
@@ -6,6 +6,8 @@ import type {
     QrTokenData,
     QuarkResponse,
     RawFileItem,
+    ResolvedShareNode,
+    ResolvedShareTree,
     ShareFileNode,
     ShareTokenData,
     TaskResponse,
@@ -46,6 +48,16 @@ export const quarkApi = {
         return data;
     },
 
+    // 手动粘贴的 Cookie 交给后端保存，换回会话 ID
+    createSession: async (cookie: string) => {
+        const { data } = await http.post<{ session: string }>('/session', { cookie });
+        return data;
+    },
+
+    deleteSession: async () => {
+        await http.delete('/session');
+    },
+
     // 分享链接
     getShareToken: async (pwdId: string, passcode: string) => {
         const { data } = await http.post<QuarkResponse<ShareTokenData>>('/share/token', { pwd_id: pwdId, passcode });
@@ -69,6 +81,12 @@ export const quarkApi = {
         return data;
     },
 
+    // 服务端一次性解析整个分享目录树
+    resolveShare: async (url: string) => {
+        const { data } = await http.post<ResolvedShareTree>('/share/resolve', { url });
+        return data;
+    },
+
     // 文件操作
     saveFiles: async (pid: string, st: string, fids: string[], tokens: string[]) => {
         const { data } = await http.post<QuarkResponse<SaveFilesData>>('/share/save', {
@@ -118,6 +136,21 @@ export const quarkApi = {
 };
 
 // 辅助函数
+export const toShareFileNodes = (nodes: ResolvedShareNode[]): ShareFileNode[] =>
+    nodes.map((node) => ({
+        fid: node.fid,
+        file_name: node.file_name,
+        size: node.size,
+        format_type: node.format_type,
+        updated_at: node.updated_at,
+        share_fid_token: node.share_fid_token,
+        isDir: node.is_dir,
+        depth: node.depth,
+        path: node.path,
+        children: toShareFileNodes(node.children),
+        expanded: node.depth === 0,
+    }));
+
 export const collectFiles = (nodes: ShareFileNode[], allFiles: ShareFileNode[] = []) => {
     for (const node of nodes) {
         if (!node.isDir) {
@@ -129,21 +162,3 @@ export const collectFiles = (nodes: ShareFileNode[], allFiles: ShareFileNode[] =
     }
     return allFiles;
 };
-
-export const parseShareUrl = (url: string) => {
-    const cleanUrl = url.replace(/\[.*?\]/g, '').trim();
-    let pwdId = '';
-    let passcode = '';
-    let pdirFid = '0';
-
-    const m1 = cleanUrl.match(/\/s\/([a-zA-Z0-9]+)/);
-    if (m1) pwdId = m1[1];
-
-    const m2 = cleanUrl.match(/[?&](pwd|passcode|password|pw)=([a-zA-Z0-9]{4})/i);
-    if (m2) passcode = m2[2];
-
-    const m3 = cleanUrl.match(/#\/list\/share\/([a-zA-Z0-9]+)/);
-    if (m3) pdirFid = m3[1];
-
-    return { pwdId, passcode, pdirFid };
-};
@@ -1,5 +1,5 @@
 import { create } from 'zustand';
-import type { ShareFileNode } from '../types/quark';
+import type { QueueJob, ShareFileNode } from '../types/quark';
 
 export interface LogEntry {
     message: string;
@@ -10,6 +10,7 @@ export interface LogEntry {
 interface ShareParseState {
     pwdId: string;
     stoken: string;
+    title: string;
     files: ShareFileNode[];
     allFiles: ShareFileNode[];
     status: string;
@@ -22,11 +23,12 @@ export interface DownloadInfo {
     downloaded: number;
     total: number;
     speed: number;
-    status: 'downloading' | 'merging' | 'done';
+    status: 'downloading' | 'paused' | 'verifying' | 'done';
 }
 
 interface QuarkState {
-    cookie: string;
+    /** 后端登录会话 ID */
+    session: string;
     toast: {
         isLoggingOut?: boolean;
         id: number;
@@ -50,9 +52,12 @@ interface QuarkState {
     selectedFids: Set<string>;
     downloadConcurrency: number;
     downloadThreads: number;
+    /** 下载时在保存目录下建立以分享标题命名的顶层目录 */
+    prefixShareTitle: boolean;
     activeDownloads: Map<string, DownloadInfo>;
+    jobs: Map<string, QueueJob>;
 
-    setCookie: (cookie: string) => void;
+    setSession: (session: string) => void;
     setCapacity: (used: number, total: number) => void;
     setCapacityLoading: (loading: boolean) => void;
     setShareInfo: (info: Partial<ShareParseState>) => void;
@@ -60,6 +65,7 @@ interface QuarkState {
     clearLogs: () => void;
     setDownloadConcurrency: (value: number) => void;
     setDownloadThreads: (value: number) => void;
+    setPrefixShareTitle: (value: boolean) => void;
     setLoggingOut: (isLoggingOut: boolean) => void;
     notify: (message: string, type: 'info' | 'success' | 'error' | 'warn', durationMs?: number) => void;
     toggleFileSelection: (fid: string) => void;
@@ -70,6 +76,8 @@ interface QuarkState {
     updateDownload: (id: string, info: Partial<DownloadInfo> & { filename: string }) => void;
     removeDownload: (id: string) => void;
     clearAllDownloads: () => void;
+    upsertJob: (job: QueueJob) => void;
+    setJobs: (jobs: QueueJob[]) => void;
     resetShareInfo: () => void;
     setShareUrl: (url: string) => void;
 }
@@ -80,8 +88,8 @@ export const useQuarkStore = create<QuarkState>((set) => ({
         message: '',
         type: '',
     },
-    cookie: localStorage.getItem('quark_cookie') || '',
-    isLoggedIn: !!localStorage.getItem('quark_cookie'),
+    session: localStorage.getItem('quark_session') || '',
+    isLoggedIn: !!localStorage.getItem('quark_session'),
     capacity: {
         used: 0,
         total: 0,
@@ -90,6 +98,7 @@ export const useQuarkStore = create<QuarkState>((set) => ({
     shareInfo: {
         pwdId: '',
         stoken: '',
+        title: '',
         files: [],
         allFiles: [],
         status: '',
@@ -106,11 +115,13 @@ export const useQuarkStore = create<QuarkState>((set) => ({
     selectedFids: new Set<string>(),
     downloadConcurrency: Number(localStorage.getItem('download_concurrency')) || 5,
     downloadThreads: Number(localStorage.getItem('download_threads')) || 999,
+    prefixShareTitle: localStorage.getItem('prefix_share_title') === '1',
     activeDownloads: new Map<string, DownloadInfo>(),
+    jobs: new Map<string, QueueJob>(),
 
-    setCookie: (cookie: string) => {
-        localStorage.setItem('quark_cookie', cookie);
-        set({ cookie, isLoggedIn: !!cookie });
+    setSession: (session: string) => {
+        localStorage.setItem('quark_session', session);
+        set({ session, isLoggedIn: !!session });
     },
     setCapacity: (used: number, total: number) => set({ capacity: { used, total, loading: false } }),
     setCapacityLoading: (loading: boolean) => set((state: QuarkState) => ({ capacity: { ...state.capacity, loading } })),
@@ -150,6 +161,10 @@ export const useQuarkStore = create<QuarkState>((set) => ({
 
         set({ downloadThreads: normalized });
     },
+    setPrefixShareTitle: (value: boolean) => {
+        localStorage.setItem('prefix_share_title', value ? '1' : '0');
+        set({ prefixShareTitle: value });
+    },
     notify: (message: string, type: 'info' | 'success' | 'error' | 'warn', durationMs?: number) => {
         const toastId = Date.now() + Math.random();
         const timeout = durationMs ?? 3000;
@@ -192,10 +207,17 @@ export const useQuarkStore = create<QuarkState>((set) => ({
         return { activeDownloads: next };
     }),
     clearAllDownloads: () => set({ activeDownloads: new Map<string, DownloadInfo>() }),
+    upsertJob: (job: QueueJob) => set((state: QuarkState) => {
+        const next = new Map(state.jobs);
+        next.set(job.id, job);
+        return { jobs: next };
+    }),
+    setJobs: (jobs: QueueJob[]) => set({ jobs: new Map(jobs.map((job) => [job.id, job])) }),
     resetShareInfo: () => set((state: QuarkState) => ({
         shareInfo: {
             pwdId: '',
             stoken: '',
+            title: '',
             files: [],
             allFiles: [],
             status: '',
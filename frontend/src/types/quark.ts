@@ -22,8 +22,9 @@ export interface QrQueryData {
     };
 }
 
+/** 扫码登录完成后由后端建立的会话，Cookie 只保存在后端 */
 export interface QrCookieData {
-    cookie: string;
+    session: string;
     user_info: unknown;
     missing_puus: boolean;
 }
@@ -46,6 +47,31 @@ export interface ShareFileNode {
     expanded: boolean;
 }
 
+/** 后端 /share/resolve 返回的节点（字段与 ShareFileNode 对应，UI 状态除外） */
+export interface ResolvedShareNode {
+    fid: string;
+    file_name: string;
+    size: number;
+    format_type: string;
+    updated_at: number;
+    share_fid_token: string;
+    is_dir: boolean;
+    depth: number;
+    path: string;
+    children: ResolvedShareNode[];
+}
+
+export interface ResolvedShareTree {
+    pwd_id: string;
+    passcode: string;
+    pdir_fid: string;
+    title: string;
+    stoken: string;
+    files: ResolvedShareNode[];
+    file_count: number;
+    dir_count: number;
+}
+
 export interface RawFileItem {
     fid: string;
     file_name: string;
@@ -74,3 +100,65 @@ export interface TaskResponse {
 export interface DownloadUrlData {
     download_url: string;
 }
+
+export type JobState = 'queued' | 'resolving' | 'downloading' | 'verifying' | 'done' | 'failed' | 'cancelled';
+
+export interface QueueJob {
+    id: string;
+    fid: string;
+    filename: string;
+    size: number;
+    state: JobState;
+    error?: string | null;
+    path?: string | null;
+}
+
+export type ConflictPolicy = 'rename' | 'overwrite' | 'skip_same_size' | 'skip_same_hash' | 'resume';
+
+/** 按时段覆盖全局限速，时间为本地 "HH:MM"，limit 单位字节/秒，null 为不限速 */
+export interface BandwidthScheduleRule {
+    start: string;
+    end: string;
+    limit: number | null;
+}
+
+export interface BandwidthSettings {
+    global_limit: number | null;
+    per_download_limit: number | null;
+    schedule: BandwidthScheduleRule[];
+}
+
+/** 出站代理，url 支持 http:// https:// socks5:// socks5h:// */
+export interface ProxyConfig {
+    url: string;
+    username?: string | null;
+    password?: string | null;
+    no_proxy?: string | null;
+}
+
+export type CdnProxyMode = 'same_as_api' | 'direct' | 'custom';
+
+export interface ProxySettings {
+    /** 为空时沿用系统代理环境变量 */
+    api: ProxyConfig | null;
+    cdn_mode: CdnProxyMode;
+    cdn: ProxyConfig | null;
+}
+
+/** 夸克 API 请求的超时、重试与按主机限速 */
+export interface NetworkSettings {
+    connect_timeout_secs: number;
+    read_timeout_secs: number;
+    max_retries: number;
+    /** 0 表示不限 */
+    requests_per_second: number;
+}
+
+/** 后端全局下载设置（get_settings / save_settings） */
+export interface DownloadSettings {
+    download_dir?: string | null;
+    conflict_policy: ConflictPolicy;
+    bandwidth: BandwidthSettings;
+    proxy: ProxySettings;
+    network: NetworkSettings;
+}
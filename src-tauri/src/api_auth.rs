@@ -0,0 +1,293 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use rand::{distributions::Alphanumeric, Rng};
+use serde_json::json;
+use std::sync::Arc;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+const TOKEN_LEN: usize = 48;
+
+/// 打包后的 webview 来源：macOS / Linux 为 tauri://localhost，Windows 为 http(s)://tauri.localhost
+const TAURI_ORIGINS: &[&str] = &[
+    "tauri://localhost",
+    "http://tauri.localhost",
+    "https://tauri.localhost",
+];
+
+/// 开发模式下前端由 Vite 提供（tauri.conf.json 的 devUrl）
+const DEV_ORIGINS: &[&str] = &["http://127.0.0.1:1420", "http://localhost:1420"];
+
+/// 无需令牌的路径（不涉及任何账号数据）
+const PUBLIC_PATHS: &[&str] = &["/api/health"];
+
+/// 内嵌服务的访问控制：每次启动随机生成的 Bearer 令牌 + 允许的来源列表。
+/// 令牌只通过 Tauri 命令交给 webview，本机其他网页既拿不到令牌，也通不过来源检查
+pub struct ApiAuth {
+    token: String,
+    origins: Vec<HeaderValue>,
+}
+
+impl ApiAuth {
+    /// 生成新令牌，只允许 Tauri webview（调试构建另加 Vite 开发服务器）的来源
+    pub fn generate() -> Self {
+        let dev = if cfg!(debug_assertions) {
+            DEV_ORIGINS
+        } else {
+            &[]
+        };
+        Self::new(random_token(), TAURI_ORIGINS.iter().chain(dev).copied())
+    }
+
+    /// 守护进程没有 webview，不放行任何浏览器来源，只认令牌；未指定令牌时随机生成
+    pub fn headless(token: Option<String>) -> Self {
+        Self::new(token.unwrap_or_else(random_token), [])
+    }
+
+    pub fn new<'a>(token: String, origins: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            token,
+            origins: origins
+                .into_iter()
+                .filter_map(|o| HeaderValue::from_str(o).ok())
+                .collect(),
+        }
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    fn origin_allowed(&self, origin: &HeaderValue) -> bool {
+        self.origins.iter().any(|o| o == origin)
+    }
+
+    fn token_matches(&self, value: Option<&HeaderValue>) -> bool {
+        value
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .is_some_and(|t| constant_time_eq(t.trim().as_bytes(), self.token.as_bytes()))
+    }
+}
+
+/// 给路由加上来源限制与令牌校验。
+/// CORS 在最外层，跨域预检只对允许的来源放行；其余请求先查来源、再查令牌
+pub fn protect<S>(router: Router<S>, auth: Arc<ApiAuth>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let cors = CorsLayer::new()
+        .allow_origin(AllowOrigin::list(auth.origins.clone()))
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+        ])
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            HeaderName::from_static("x-session"),
+        ]);
+
+    router
+        .layer(middleware::from_fn_with_state(auth, require_auth))
+        .layer(cors)
+}
+
+async fn require_auth(State(auth): State<Arc<ApiAuth>>, req: Request, next: Next) -> Response {
+    // 浏览器发出的跨域请求一定带 Origin；不带 Origin 的是本机程序（如命令行工具），只凭令牌放行
+    if let Some(origin) = req.headers().get(header::ORIGIN) {
+        if !auth.origin_allowed(origin) {
+            println!("[api] 拒绝来源 {:?} 的请求: {}", origin, req.uri().path());
+            return reject(StatusCode::FORBIDDEN, "来源不被允许");
+        }
+    }
+    if !PUBLIC_PATHS.contains(&req.uri().path())
+        && !auth.token_matches(req.headers().get(header::AUTHORIZATION))
+    {
+        return reject(StatusCode::UNAUTHORIZED, "缺少或错误的访问令牌");
+    }
+    next.run(req).await
+}
+
+fn reject(status: StatusCode, message: &str) -> Response {
+    (
+        status,
+        [(header::CONTENT_TYPE, "application/json; charset=utf-8")],
+        json!({ "error": message, "kind": "unauthorized" }).to_string(),
+    )
+        .into_response()
+}
+
+fn random_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(TOKEN_LEN)
+        .map(char::from)
+        .collect()
+}
+
+/// 逐字节比较不提前返回，避免通过响应时间猜出令牌
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        routing::{get, post},
+    };
+    use tower::ServiceExt;
+
+    const TOKEN: &str = "test-token";
+    const APP_ORIGIN: &str = "tauri://localhost";
+    const FOREIGN_ORIGIN: &str = "https://evil.example";
+
+    fn app() -> Router {
+        let router = Router::new()
+            .route("/api/health", get(|| async { "ok" }))
+            .route("/api/file/delete", post(|| async { "deleted" }));
+        protect(
+            router,
+            Arc::new(ApiAuth::new(TOKEN.into(), TAURI_ORIGINS.iter().copied())),
+        )
+    }
+
+    async fn send(
+        method: Method,
+        path: &str,
+        origin: Option<&str>,
+        token: Option<&str>,
+    ) -> Response {
+        let mut req = Request::builder().method(method).uri(path);
+        if let Some(origin) = origin {
+            req = req.header(header::ORIGIN, origin);
+        }
+        if let Some(token) = token {
+            req = req.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        app()
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn app_origin_with_token_is_allowed() {
+        let resp = send(
+            Method::POST,
+            "/api/file/delete",
+            Some(APP_ORIGIN),
+            Some(TOKEN),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers()
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .unwrap(),
+            APP_ORIGIN
+        );
+    }
+
+    #[tokio::test]
+    async fn foreign_origin_is_refused_even_with_token() {
+        let resp = send(
+            Method::POST,
+            "/api/file/delete",
+            Some(FOREIGN_ORIGIN),
+            Some(TOKEN),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(resp
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn foreign_origin_cannot_read_health() {
+        let resp = send(Method::GET, "/api/health", Some(FOREIGN_ORIGIN), None).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn foreign_preflight_gets_no_cors_grant() {
+        let req = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/api/file/delete")
+            .header(header::ORIGIN, FOREIGN_ORIGIN)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .header(
+                header::ACCESS_CONTROL_REQUEST_HEADERS,
+                "authorization,x-session",
+            )
+            .body(Body::empty())
+            .unwrap();
+        let resp = app().oneshot(req).await.unwrap();
+        assert!(resp
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn missing_or_wrong_token_is_rejected() {
+        let resp = send(Method::POST, "/api/file/delete", Some(APP_ORIGIN), None).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = send(
+            Method::POST,
+            "/api/file/delete",
+            Some(APP_ORIGIN),
+            Some("guess"),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = send(Method::POST, "/api/file/delete", None, None).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn local_client_without_origin_needs_only_token() {
+        let resp = send(Method::POST, "/api/file/delete", None, Some(TOKEN)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = send(Method::GET, "/api/health", None, None).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn headless_auth_refuses_every_browser_origin() {
+        let router = Router::new().route("/api/jobs", get(|| async { "[]" }));
+        let app = protect(router, Arc::new(ApiAuth::headless(Some(TOKEN.into()))));
+        let req = |origin: Option<&str>| {
+            let mut req = Request::builder()
+                .uri("/api/jobs")
+                .header(header::AUTHORIZATION, format!("Bearer {}", TOKEN));
+            if let Some(origin) = origin {
+                req = req.header(header::ORIGIN, origin);
+            }
+            req.body(Body::empty()).unwrap()
+        };
+        let resp = app.clone().oneshot(req(Some(APP_ORIGIN))).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let resp = app.oneshot(req(None)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn generated_tokens_are_random() {
+        let a = ApiAuth::generate();
+        let b = ApiAuth::generate();
+        assert_eq!(a.token().len(), TOKEN_LEN);
+        assert_ne!(a.token(), b.token());
+    }
+}
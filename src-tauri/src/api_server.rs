@@ -1,30 +1,49 @@
 use axum::{
-    Router,
-    extract::Query,
-    http::{HeaderMap, StatusCode, header},
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     routing::{get, post},
+    Router,
 };
 use reqwest::Method;
 use serde::Deserialize;
-use serde_json::{Value, json};
-use tower_http::cors::{CorsLayer, Any};
+use serde_json::{json, Value};
+use std::sync::Arc;
 
-use crate::quark_client;
+use crate::api_auth::{self, ApiAuth};
+use crate::backend::Backend;
+use crate::history::HistoryFilter;
+use crate::quark_client::{self, QuarkError, ShareNode};
+use crate::queue::{EnqueueRequest, ShareFile};
+use crate::session::CookieJar;
+use crate::settings::{ConflictPolicy, Settings};
 
-pub fn create_router() -> Router {
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any)
-        .expose_headers([header::HeaderName::from_static("x-append-cookie")]);
+/// 路由共享的应用状态：窗口与守护进程用的是同一个后端
+pub type AppState = Arc<Backend>;
 
-    Router::new()
+pub fn create_router(state: AppState, auth: Arc<ApiAuth>) -> Router {
+    let router = Router::new()
         .route("/api/health", get(health))
         .route("/api/qrlogin/token", get(qr_token))
         .route("/api/qrlogin/query", get(qr_query))
         .route("/api/qrlogin/cookie", get(qr_cookie))
         .route("/api/logout", post(logout))
+        .route("/api/session", post(session_create).delete(session_delete))
+        .route("/api/history", get(history_list).delete(history_clear))
+        .route("/api/jobs", get(jobs_list).post(jobs_create))
+        .route("/api/jobs/{id}", get(job_get).delete(job_cancel))
+        .route("/api/jobs/{id}/pause", post(job_pause))
+        .route("/api/jobs/{id}/resume", post(job_resume))
+        .route("/api/downloads", get(downloads_list))
+        .route(
+            "/api/queue/limits",
+            get(queue_limits_get).put(queue_limits_put),
+        )
+        .route(
+            "/api/settings",
+            get(settings_get).put(settings_put).patch(settings_patch),
+        )
+        .route("/api/share/resolve", post(share_resolve))
         // API 代理路由
         .route("/api/share/token", post(api_proxy))
         .route("/api/share/detail", get(api_proxy))
@@ -32,8 +51,8 @@ pub fn create_router() -> Router {
         .route("/api/task", get(api_proxy))
         .route("/api/file/download", post(api_proxy))
         .route("/api/file/delete", post(api_proxy))
-        .route("/api/member", get(api_proxy))
-        .layer(cors)
+        .route("/api/member", get(api_proxy));
+    api_auth::protect(router, auth).with_state(state)
 }
 
 fn json_response(status: StatusCode, body: Value) -> Response {
@@ -45,26 +64,53 @@ fn json_response(status: StatusCode, body: Value) -> Response {
         .into_response()
 }
 
-fn extract_cookie(headers: &HeaderMap) -> String {
+/// 夸克接口错误统一映射为 HTTP 状态 + { error, kind, code }
+fn error_response(e: QuarkError) -> Response {
+    let status = match &e {
+        QuarkError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+        QuarkError::AuthExpired(_) => StatusCode::UNAUTHORIZED,
+        QuarkError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+        QuarkError::CapacityExceeded(_) => StatusCode::INSUFFICIENT_STORAGE,
+        QuarkError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+        QuarkError::Network(_)
+        | QuarkError::Http { .. }
+        | QuarkError::Api { .. }
+        | QuarkError::Decode(_) => StatusCode::BAD_GATEWAY,
+    };
+    json_response(
+        status,
+        json!({ "error": e.to_string(), "kind": e.kind(), "code": e.code() }),
+    )
+}
+
+/// 请求头 x-session 中的会话 ID，未登录时为空
+fn extract_session(headers: &HeaderMap) -> &str {
     headers
-        .get("x-cookie")
+        .get("x-session")
         .and_then(|v| v.to_str().ok())
         .unwrap_or("")
-        .to_string()
+}
+
+/// 由会话 ID 取出服务端保存的 Cookie
+fn session_cookie(state: &AppState, headers: &HeaderMap) -> Result<String, QuarkError> {
+    state.sessions.cookie(extract_session(headers))
 }
 
 // ==================== Health ====================
 
 async fn health() -> Response {
-    json_response(StatusCode::OK, json!({ "ok": true, "service": "quark-api-rust" }))
+    json_response(
+        StatusCode::OK,
+        json!({ "ok": true, "service": "quark-api-rust" }),
+    )
 }
 
 // ==================== 扫码登录 ====================
 
-async fn qr_token() -> Response {
-    match quark_client::qr_get_token().await {
+async fn qr_token(State(state): State<AppState>) -> Response {
+    match quark_client::qr_get_token(&state.clients.api()).await {
         Ok(data) => json_response(StatusCode::OK, data),
-        Err(e) => json_response(StatusCode::BAD_GATEWAY, json!({ "error": e })),
+        Err(e) => error_response(e),
     }
 }
 
@@ -73,18 +119,21 @@ struct QrQueryParams {
     token: Option<String>,
 }
 
-async fn qr_query(Query(params): Query<QrQueryParams>) -> Response {
+async fn qr_query(State(state): State<AppState>, Query(params): Query<QrQueryParams>) -> Response {
     let token = match params.token {
         Some(t) if !t.is_empty() => t,
         _ => return json_response(StatusCode::BAD_REQUEST, json!({ "error": "Missing token" })),
     };
 
-    match quark_client::qr_query_status(&token).await {
+    match quark_client::qr_query_status(&state.clients.api(), &token).await {
         Ok(result) => {
             let body: Value = serde_json::from_slice(&result.body).unwrap_or(Value::Null);
-            json_response(StatusCode::from_u16(result.status).unwrap_or(StatusCode::OK), body)
+            json_response(
+                StatusCode::from_u16(result.status).unwrap_or(StatusCode::OK),
+                body,
+            )
         }
-        Err(e) => json_response(StatusCode::BAD_GATEWAY, json!({ "error": e })),
+        Err(e) => error_response(e),
     }
 }
 
@@ -93,31 +142,425 @@ struct QrCookieParams {
     service_ticket: Option<String>,
 }
 
-async fn qr_cookie(Query(params): Query<QrCookieParams>) -> Response {
+async fn qr_cookie(
+    State(state): State<AppState>,
+    Query(params): Query<QrCookieParams>,
+) -> Response {
     let st = match params.service_ticket {
         Some(s) if !s.is_empty() => s,
-        _ => return json_response(StatusCode::BAD_REQUEST, json!({ "error": "Missing service_ticket" })),
+        _ => {
+            return json_response(
+                StatusCode::BAD_REQUEST,
+                json!({ "error": "Missing service_ticket" }),
+            )
+        }
     };
 
-    match quark_client::qr_get_cookie(&st).await {
-        Ok(data) => json_response(StatusCode::OK, data),
-        Err(e) => json_response(StatusCode::BAD_GATEWAY, json!({ "error": e })),
+    let mut data = match quark_client::qr_get_cookie(&state.clients.api(), &st).await {
+        Ok(data) => data,
+        Err(e) => return error_response(e),
+    };
+    // Cookie 留在服务端，只把会话 ID 交给前端
+    let cookie = data
+        .as_object_mut()
+        .and_then(|obj| obj.remove("cookie"))
+        .and_then(|v| v.as_str().map(CookieJar::parse))
+        .unwrap_or_default();
+    match state.sessions.create(cookie) {
+        Ok(session) => {
+            data["session"] = json!(session);
+            json_response(StatusCode::OK, data)
+        }
+        Err(e) => error_response(QuarkError::Decode(format!("登录未返回 Cookie: {}", e))),
+    }
+}
+
+// ==================== 登录会话 ====================
+
+#[derive(Deserialize)]
+struct SessionCreateBody {
+    cookie: Option<String>,
+}
+
+/// 用手动粘贴的 Cookie 建立会话
+async fn session_create(
+    State(state): State<AppState>,
+    axum::Json(body): axum::Json<SessionCreateBody>,
+) -> Response {
+    let jar = CookieJar::parse(body.cookie.as_deref().unwrap_or(""));
+    if jar.is_empty() {
+        return error_response(QuarkError::InvalidInput("Missing cookie".into()));
+    }
+    match state.sessions.create(jar) {
+        Ok(session) => json_response(StatusCode::OK, json!({ "session": session })),
+        Err(e) => json_response(StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": e })),
+    }
+}
+
+/// 只丢弃本地会话，不通知夸克服务端
+async fn session_delete(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    match state.sessions.remove(extract_session(&headers)) {
+        Ok(()) => json_response(StatusCode::OK, json!({ "ok": true })),
+        Err(e) => json_response(StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": e })),
     }
 }
 
 // ==================== 退出登录 ====================
 
-async fn logout(headers: HeaderMap) -> Response {
-    let cookie = extract_cookie(&headers);
-    match quark_client::logout(&cookie).await {
-        Ok(data) => json_response(StatusCode::OK, data),
+async fn logout(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let cookie = match session_cookie(&state, &headers) {
+        Ok(cookie) => cookie,
+        Err(e) => return error_response(e),
+    };
+    match quark_client::logout(&state.clients.api(), &cookie).await {
+        Ok(data) => {
+            // 远端注销无论是否确认成功，本地会话都不再保留
+            if let Err(e) = state.sessions.remove(extract_session(&headers)) {
+                println!("[session] {}", e);
+            }
+            json_response(StatusCode::OK, data)
+        }
+        Err(e) => error_response(e),
+    }
+}
+
+// ==================== 分享解析 ====================
+
+#[derive(Deserialize)]
+struct ShareResolveBody {
+    url: Option<String>,
+}
+
+/// 服务端一次性解析整个分享目录树
+async fn share_resolve(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    axum::Json(body): axum::Json<ShareResolveBody>,
+) -> Response {
+    let url = match body.url {
+        Some(u) if !u.trim().is_empty() => u,
+        _ => return error_response(QuarkError::InvalidInput("Missing url".into())),
+    };
+    let cookie = match session_cookie(&state, &headers) {
+        Ok(cookie) => cookie,
+        Err(e) => return error_response(e),
+    };
+    match quark_client::resolve_share(&state.clients.api(), &cookie, &url).await {
+        Ok(tree) => json_response(StatusCode::OK, json!(tree)),
+        Err(e) => error_response(e),
+    }
+}
+
+// ==================== 下载历史 ====================
+
+async fn history_list(
+    State(state): State<AppState>,
+    Query(filter): Query<HistoryFilter>,
+) -> Response {
+    let records = state.history.query(&filter);
+    json_response(
+        StatusCode::OK,
+        json!({ "total": records.len(), "list": records }),
+    )
+}
+
+/// 删除符合条件的已结束记录（不带条件时清空全部已结束记录）
+async fn history_clear(
+    State(state): State<AppState>,
+    Query(filter): Query<HistoryFilter>,
+) -> Response {
+    match state.history.clear(&filter) {
+        Ok(removed) => json_response(StatusCode::OK, json!({ "removed": removed })),
+        Err(e) => json_response(StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": e })),
+    }
+}
+
+// ==================== 下载队列 ====================
+
+async fn jobs_list(State(state): State<AppState>) -> Response {
+    let jobs = state.queue.list();
+    json_response(StatusCode::OK, json!({ "total": jobs.len(), "list": jobs }))
+}
+
+#[derive(Deserialize)]
+struct JobsCreateBody {
+    /// 分享链接，可带 ?pwd= 提取码
+    url: Option<String>,
+    /// 要下载的文件或目录（目录表示其下全部文件），省略时下载整个分享
+    #[serde(default)]
+    fids: Vec<String>,
+    /// 保存目录，省略时用设置中的默认目录
+    save_dir: Option<String>,
+    /// 是否在保存目录下多建一级以分享标题命名的目录，默认是
+    title_dir: Option<bool>,
+    conflict_policy: Option<ConflictPolicy>,
+    thread_count: Option<usize>,
+    retry_count: Option<u32>,
+}
+
+/// 解析分享链接并把选中的文件加入下载队列，返回新建的任务
+async fn jobs_create(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    axum::Json(body): axum::Json<JobsCreateBody>,
+) -> Response {
+    let url = match body.url {
+        Some(u) if !u.trim().is_empty() => u,
+        _ => return error_response(QuarkError::InvalidInput("Missing url".into())),
+    };
+    if state.queue.is_closing() {
+        return json_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            json!({ "error": "服务正在退出，不再接收新任务" }),
+        );
+    }
+    // 下载要先转存到自己的网盘，必须登录
+    let cookie = match session_cookie(&state, &headers) {
+        Ok(cookie) if cookie.is_empty() => {
+            return error_response(QuarkError::AuthExpired("未登录".into()))
+        }
+        Ok(cookie) => cookie,
+        Err(e) => return error_response(e),
+    };
+    let tree = match quark_client::resolve_share(&state.clients.api(), &cookie, &url).await {
+        Ok(tree) => tree,
+        Err(e) => return error_response(e),
+    };
+
+    let files = match select_files(&tree.files, &body.fids) {
+        Ok(files) if files.is_empty() => {
+            return error_response(QuarkError::InvalidInput("没有可下载的文件".into()))
+        }
+        Ok(files) => files,
+        Err(e) => return error_response(QuarkError::InvalidInput(e)),
+    };
+    let request = EnqueueRequest {
+        session: extract_session(&headers).to_string(),
+        pwd_id: tree.link.pwd_id,
+        stoken: tree.stoken,
+        files: files
+            .into_iter()
+            .map(|node| ShareFile {
+                fid: node.fid.clone(),
+                share_fid_token: node.share_fid_token.clone(),
+                file_name: node.file_name.clone(),
+                size: node.size,
+                path: Some(node.path.clone()),
+            })
+            .collect(),
+        save_dir: body.save_dir,
+        share_title: body.title_dir.unwrap_or(true).then_some(tree.title),
+        conflict_policy: body.conflict_policy,
+        thread_count: body.thread_count,
+        retry_count: body.retry_count,
+    };
+    match state.queue.enqueue(&state, request) {
+        Ok(jobs) => json_response(
+            StatusCode::CREATED,
+            json!({ "total": jobs.len(), "list": jobs }),
+        ),
+        Err(e) => error_response(QuarkError::InvalidInput(e)),
+    }
+}
+
+/// 按 fid 选出要下载的文件；选中目录即选中其下全部文件，未选择时取整个分享
+fn select_files<'a>(nodes: &'a [ShareNode], fids: &[String]) -> Result<Vec<&'a ShareNode>, String> {
+    let mut files = Vec::new();
+    if fids.is_empty() {
+        collect_files(nodes, &mut files);
+        return Ok(files);
+    }
+    for fid in fids {
+        let node = find_node(nodes, fid).ok_or_else(|| format!("分享中没有该文件: {}", fid))?;
+        collect_files(std::slice::from_ref(node), &mut files);
+    }
+    // 同时选中目录和其中的文件时只下载一次
+    let mut seen = std::collections::HashSet::new();
+    files.retain(|node| seen.insert(node.fid.as_str()));
+    Ok(files)
+}
+
+fn find_node<'a>(nodes: &'a [ShareNode], fid: &str) -> Option<&'a ShareNode> {
+    nodes.iter().find_map(|node| {
+        if node.fid == fid {
+            Some(node)
+        } else {
+            find_node(&node.children, fid)
+        }
+    })
+}
+
+fn collect_files<'a>(nodes: &'a [ShareNode], out: &mut Vec<&'a ShareNode>) {
+    for node in nodes {
+        if node.is_dir {
+            collect_files(&node.children, out);
+        } else {
+            out.push(node);
+        }
+    }
+}
+
+fn job_not_found(id: &str) -> Response {
+    json_response(
+        StatusCode::NOT_FOUND,
+        json!({ "error": format!("任务不存在: {}", id) }),
+    )
+}
+
+/// 任务状态；正在下载时附带下载的控制状态（running / paused）与最近一次进度
+async fn job_get(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    let Some(job) = state.queue.get(&id) else {
+        return job_not_found(&id);
+    };
+    let download = state.registry.get(&id).map(|handle| handle.info());
+    let mut body = json!(job);
+    body["download"] = json!(download);
+    json_response(StatusCode::OK, body)
+}
+
+/// 取消任务：排队中的不再开始，下载中的中断并删除部分数据
+async fn job_cancel(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    let Some(job) = state.queue.get(&id) else {
+        return job_not_found(&id);
+    };
+    match state.queue.cancel(&state, &id) {
+        Some(job) => json_response(StatusCode::OK, json!(job)),
+        None => json_response(
+            StatusCode::CONFLICT,
+            json!({ "error": format!("任务已结束: {}", id), "state": job.state }),
+        ),
+    }
+}
+
+/// 暂停正在下载的任务：各连接在当前数据块写完后断开，部分数据保留
+async fn job_pause(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    control_job(&state, &id, true)
+}
+
+/// 恢复已暂停的任务，各块从已落盘的位置继续
+async fn job_resume(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    control_job(&state, &id, false)
+}
+
+/// 暂停 / 恢复只作用于正在下载的任务；排队、解析中或已结束的任务返回 409
+fn control_job(state: &AppState, id: &str, pause: bool) -> Response {
+    let Some(job) = state.queue.get(id) else {
+        return job_not_found(id);
+    };
+    let Some(handle) = state.registry.get(id) else {
+        return json_response(
+            StatusCode::CONFLICT,
+            json!({ "error": format!("任务不在下载中: {}", id), "state": job.state }),
+        );
+    };
+    let changed = if pause {
+        handle.pause()
+    } else {
+        handle.resume()
+    };
+    println!(
+        "[download] {} {} ({})",
+        if pause { "暂停" } else { "恢复" },
+        id,
+        handle.filename
+    );
+    let key = if pause { "paused" } else { "resumed" };
+    json_response(
+        StatusCode::OK,
+        json!({ "id": id, key: changed, "state": handle.control() }),
+    )
+}
+
+/// 正在进行的下载及其最近一次进度
+async fn downloads_list(State(state): State<AppState>) -> Response {
+    json_response(StatusCode::OK, json!(state.registry.list()))
+}
+
+async fn queue_limits_get(State(state): State<AppState>) -> Response {
+    json_response(StatusCode::OK, json!(state.queue.limits()))
+}
+
+#[derive(Deserialize)]
+struct QueueLimitsBody {
+    file_concurrency: Option<usize>,
+    max_connections: Option<usize>,
+}
+
+/// 调整文件并发数与全局连接数上限，对正在运行的任务立即生效
+async fn queue_limits_put(
+    State(state): State<AppState>,
+    axum::Json(body): axum::Json<QueueLimitsBody>,
+) -> Response {
+    state
+        .queue
+        .set_limits(body.file_concurrency, body.max_connections);
+    json_response(StatusCode::OK, json!(state.queue.limits()))
+}
+
+// ==================== 下载设置 ====================
+
+async fn settings_get(State(state): State<AppState>) -> Response {
+    json_response(StatusCode::OK, json!(state.settings.get()))
+}
+
+/// 整体替换设置，限速对进行中的下载立即生效
+async fn settings_put(
+    State(state): State<AppState>,
+    axum::Json(body): axum::Json<Settings>,
+) -> Response {
+    save_settings(&state, body)
+}
+
+/// 按 JSON Merge Patch 修改部分设置，例如只改 {"bandwidth": {"global_limit": 5242880}}
+async fn settings_patch(
+    State(state): State<AppState>,
+    axum::Json(patch): axum::Json<Value>,
+) -> Response {
+    let mut current = json!(state.settings.get());
+    merge_patch(&mut current, patch);
+    match serde_json::from_value::<Settings>(current) {
+        Ok(settings) => save_settings(&state, settings),
+        Err(e) => error_response(QuarkError::InvalidInput(format!("设置格式错误: {}", e))),
+    }
+}
+
+fn save_settings(state: &AppState, settings: Settings) -> Response {
+    match state.settings.save(settings) {
+        Ok(saved) => {
+            state.bandwidth.apply(saved.bandwidth.clone());
+            if let Err(e) = state.clients.configure(&saved) {
+                return json_response(StatusCode::INTERNAL_SERVER_ERROR, json!({ "error": e }));
+            }
+            json_response(StatusCode::OK, json!(saved))
+        }
         Err(e) => json_response(StatusCode::BAD_REQUEST, json!({ "error": e })),
     }
 }
 
+/// RFC 7396：对象逐键合并，null 表示删除该键，其他值直接替换
+fn merge_patch(target: &mut Value, patch: Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+    if !target.is_object() {
+        *target = json!({});
+    }
+    if let Value::Object(map) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                map.remove(&key);
+            } else {
+                merge_patch(map.entry(key).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
 // ==================== API 代理 ====================
 
 async fn api_proxy(
+    State(state): State<AppState>,
     method: axum::http::Method,
     uri: axum::http::Uri,
     headers: HeaderMap,
@@ -128,10 +571,18 @@ async fn api_proxy(
 
     let route = match routes.get(path) {
         Some(r) => r,
-        None => return json_response(StatusCode::NOT_FOUND, json!({ "error": "Not Found", "path": path })),
+        None => {
+            return json_response(
+                StatusCode::NOT_FOUND,
+                json!({ "error": "Not Found", "path": path }),
+            )
+        }
     };
 
-    let cookie = extract_cookie(&headers);
+    let cookie = match session_cookie(&state, &headers) {
+        Ok(cookie) => cookie,
+        Err(e) => return error_response(e),
+    };
     let query = uri.query().unwrap_or("").to_string();
 
     let req_method = match method {
@@ -139,9 +590,10 @@ async fn api_proxy(
         _ => route.method.clone(),
     };
 
-    let body_opt = if body.is_empty() { None } else { Some(body.into()) };
+    let body_opt = if body.is_empty() { None } else { Some(body) };
 
     match quark_client::proxy_request(
+        &state.clients.api(),
         route.path,
         req_method,
         Some(&cookie),
@@ -153,46 +605,190 @@ async fn api_proxy(
     .await
     {
         Ok(result) => {
-            let mut resp_headers = vec![(
-                header::CONTENT_TYPE,
-                "application/json; charset=utf-8".to_string(),
-            )];
-
-            // 检查 __puus cookie
-            if let Some(set_cookie) = result.headers.get("set-cookie") {
-                if let Ok(sc_str) = set_cookie.to_str() {
-                    if sc_str.starts_with("__puus=") {
-                        let puus_val = sc_str.split(';').next().unwrap_or("");
-                        resp_headers.push((
-                            header::HeaderName::from_static("x-append-cookie"),
-                            puus_val.to_string(),
-                        ));
-                        println!("[api-proxy] 捕获到 __puus cookie!");
-                    }
-                }
-            }
-            // 也检查多个 set-cookie
-            for val in result.headers.get_all("set-cookie") {
-                if let Ok(sc_str) = val.to_str() {
-                    if sc_str.starts_with("__puus=") {
-                        let puus_val = sc_str.split(';').next().unwrap_or("");
-                        resp_headers.push((
-                            header::HeaderName::from_static("x-append-cookie"),
-                            puus_val.to_string(),
-                        ));
-                    }
-                }
-            }
+            // 夸克下发的 Cookie（包括续期的 __puus）全部并入会话
+            state.sessions.merge(
+                extract_session(&headers),
+                result
+                    .headers
+                    .get_all(header::SET_COOKIE)
+                    .iter()
+                    .filter_map(|v| v.to_str().ok()),
+            );
 
             let status = StatusCode::from_u16(result.status).unwrap_or(StatusCode::OK);
-            let mut response = (status, result.body.to_vec()).into_response();
-            for (name, value) in resp_headers {
-                if let Ok(v) = value.parse() {
-                    response.headers_mut().insert(name, v);
-                }
-            }
-            response
+            (
+                status,
+                [(header::CONTENT_TYPE, "application/json; charset=utf-8")],
+                result.body.to_vec(),
+            )
+                .into_response()
+        }
+        Err(e) => error_response(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use quark_core::progress::DownloadProgress;
+    use tower::ServiceExt;
+
+    const TOKEN: &str = "test-token";
+
+    fn node(fid: &str, path: &str, children: Vec<ShareNode>) -> ShareNode {
+        ShareNode {
+            fid: fid.into(),
+            file_name: path.rsplit('/').next().unwrap_or(path).into(),
+            size: 1,
+            format_type: String::new(),
+            updated_at: 0,
+            share_fid_token: format!("token-{}", fid),
+            is_dir: !children.is_empty(),
+            depth: path.matches('/').count(),
+            path: path.into(),
+            children,
+        }
+    }
+
+    fn share() -> Vec<ShareNode> {
+        vec![
+            node(
+                "d1",
+                "season1",
+                vec![
+                    node("f1", "season1/e01.mkv", vec![]),
+                    node("f2", "season1/e02.mkv", vec![]),
+                ],
+            ),
+            node("f3", "readme.txt", vec![]),
+        ]
+    }
+
+    fn fids(files: &[&ShareNode]) -> Vec<String> {
+        files.iter().map(|f| f.fid.clone()).collect()
+    }
+
+    #[test]
+    fn selects_whole_share_or_chosen_entries() {
+        let nodes = share();
+        assert_eq!(
+            fids(&select_files(&nodes, &[]).unwrap()),
+            ["f1", "f2", "f3"]
+        );
+        // 选中目录即选中其下全部文件，重复选中的文件只保留一次
+        let chosen = select_files(&nodes, &["f2".into(), "d1".into()]).unwrap();
+        assert_eq!(fids(&chosen), ["f2", "f1"]);
+        assert!(select_files(&nodes, &["missing".into()]).is_err());
+    }
+
+    /// 数据目录随返回的 TempDir 一起删除，测试期间要一直持有
+    fn app() -> (tempfile::TempDir, Router) {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = Backend::open(
+            dir.path(),
+            Arc::new(|_: DownloadProgress| {}),
+            Arc::new(|_: &crate::queue::Job| {}),
+        )
+        .unwrap();
+        let router = create_router(backend, Arc::new(ApiAuth::headless(Some(TOKEN.into()))));
+        (dir, router)
+    }
+
+    async fn send(app: &Router, method: &str, path: &str, body: &str) -> StatusCode {
+        let req = Request::builder()
+            .method(method)
+            .uri(path)
+            .header(header::AUTHORIZATION, format!("Bearer {}", TOKEN))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        app.clone().oneshot(req).await.unwrap().status()
+    }
+
+    #[test]
+    fn quark_errors_map_to_http_statuses() {
+        let cases = [
+            (
+                QuarkError::InvalidInput(String::new()),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                QuarkError::AuthExpired(String::new()),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                QuarkError::RateLimited {
+                    code: 23018,
+                    message: String::new(),
+                },
+                StatusCode::TOO_MANY_REQUESTS,
+            ),
+            (
+                QuarkError::CapacityExceeded(String::new()),
+                StatusCode::INSUFFICIENT_STORAGE,
+            ),
+            (
+                QuarkError::Timeout(String::new()),
+                StatusCode::GATEWAY_TIMEOUT,
+            ),
+            (QuarkError::Network(String::new()), StatusCode::BAD_GATEWAY),
+            (
+                QuarkError::Http {
+                    status: 500,
+                    message: String::new(),
+                },
+                StatusCode::BAD_GATEWAY,
+            ),
+            (
+                QuarkError::Api {
+                    code: 41004,
+                    message: String::new(),
+                },
+                StatusCode::BAD_GATEWAY,
+            ),
+            (QuarkError::Decode(String::new()), StatusCode::BAD_GATEWAY),
+        ];
+        for (e, status) in cases {
+            let kind = e.kind();
+            assert_eq!(error_response(e).status(), status, "{}", kind);
         }
-        Err(e) => json_response(StatusCode::BAD_GATEWAY, json!({ "error": e })),
+    }
+
+    #[tokio::test]
+    async fn unknown_jobs_are_not_found() {
+        let (_dir, app) = app();
+        assert_eq!(send(&app, "GET", "/api/jobs", "").await, StatusCode::OK);
+        assert_eq!(
+            send(&app, "GET", "/api/jobs/nope", "").await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            send(&app, "DELETE", "/api/jobs/nope", "").await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            send(&app, "POST", "/api/jobs/nope/pause", "").await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            send(&app, "POST", "/api/jobs/nope/resume", "").await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn submitting_requires_url_and_session() {
+        let (_dir, app) = app();
+        assert_eq!(
+            send(&app, "POST", "/api/jobs", "{}").await,
+            StatusCode::BAD_REQUEST
+        );
+        let body = r#"{"url":"https://pan.quark.cn/s/abcdef"}"#;
+        assert_eq!(
+            send(&app, "POST", "/api/jobs", body).await,
+            StatusCode::UNAUTHORIZED
+        );
     }
 }
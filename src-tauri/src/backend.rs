@@ -0,0 +1,75 @@
+use crate::bandwidth::BandwidthLimit;
+use crate::clients::HttpClients;
+use crate::history::HistoryStore;
+use crate::queue::{JobQueue, JobReporter};
+use crate::registry::DownloadRegistry;
+use crate::session::SessionStore;
+use crate::settings::SettingsStore;
+use quark_core::progress::ProgressReporter;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 退出时等待下载落盘续传状态的最长时间
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 窗口与守护进程共用的后端：设置、会话、下载队列与正在进行的下载。
+/// 不依赖 Tauri 窗口，任务与进度的推送方式由创建方决定
+pub struct Backend {
+    pub settings: Arc<SettingsStore>,
+    pub bandwidth: Arc<BandwidthLimit>,
+    pub clients: Arc<HttpClients>,
+    pub sessions: Arc<SessionStore>,
+    pub history: Arc<HistoryStore>,
+    pub registry: DownloadRegistry,
+    pub queue: JobQueue,
+    pub progress: Arc<dyn ProgressReporter>,
+    pub jobs: Arc<dyn JobReporter>,
+}
+
+impl Backend {
+    /// 从数据目录载入设置、会话与下载历史，并把上次未完成的任务重新排队
+    pub fn open(
+        data_dir: &Path,
+        progress: Arc<dyn ProgressReporter>,
+        jobs: Arc<dyn JobReporter>,
+    ) -> Result<Arc<Self>, String> {
+        if let Err(e) = std::fs::create_dir_all(data_dir) {
+            println!("[history] 创建数据目录失败: {}", e);
+        }
+        let settings = Arc::new(SettingsStore::open(data_dir.join("settings.json")));
+        let bandwidth = Arc::new(BandwidthLimit::new(settings.get().bandwidth));
+        let clients = Arc::new(HttpClients::new(&settings.get())?);
+        tauri::async_runtime::spawn(Arc::clone(&bandwidth).run_schedule());
+        let backend = Arc::new(Self {
+            settings,
+            bandwidth,
+            clients,
+            sessions: Arc::new(SessionStore::open(data_dir.join("sessions.json"))),
+            history: Arc::new(HistoryStore::open(data_dir.join("history.jsonl"))),
+            registry: DownloadRegistry::default(),
+            queue: JobQueue::default(),
+            progress,
+            jobs,
+        });
+        backend.queue.restore(&backend, backend.history.pending());
+        Ok(backend)
+    }
+
+    /// 停止接收新任务并中断所有下载，等各下载写完续传状态后返回。
+    /// 未完成的任务在历史中保持未结束，下次启动时从已落盘的位置继续
+    pub async fn shutdown(&self) {
+        self.queue.close();
+        let count = self.registry.stop_all();
+        println!("[download] 中断全部下载 ({} 个)，等待保存续传状态", count);
+
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+        while self.queue.running() > 0 || !self.registry.list().is_empty() {
+            if Instant::now() >= deadline {
+                println!("[download] 等待超时，仍有下载未退出");
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+}
@@ -0,0 +1,288 @@
+//! --daemon 模式：不创建窗口，把桌面应用的 HTTP 接口挂到指定地址上，
+//! 供没有桌面环境的服务器通过 HTTP 管理下载队列、查看下载和修改设置
+
+use crate::api_auth::ApiAuth;
+use crate::api_server;
+use crate::backend::Backend;
+use crate::queue::{Job, JobState};
+use quark_core::progress::DownloadProgress;
+use quark_core::qr_login::{QrLogin, QR_LIFETIME};
+use quark_core::quark_client;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const USAGE: &str = "\
+用法: quark-downloader-pro --daemon [选项]
+
+选项:
+  --listen <地址:端口>             监听地址，默认 127.0.0.1:3000
+  --data-dir <目录>                数据目录（设置、会话、下载历史），默认与桌面应用相同
+  --login                          启动前在终端显示二维码扫码登录，登录会话保存在数据目录中
+  --invert                         浅色背景终端下配合 --login 使用，反色绘制二维码
+  -h, --help                       显示本帮助
+
+环境变量:
+  QUARK_API_TOKEN                  访问令牌，未设置时每次启动随机生成并打印
+
+收到 Ctrl+C 或 SIGTERM 后中断所有下载并保存续传状态，下次启动时未完成的任务自动继续";
+
+/// 与桌面应用的 app 数据目录一致（tauri.conf.json 的 identifier）
+const APP_IDENTIFIER: &str = "com.quark.downloader";
+const DEFAULT_LISTEN: &str = "127.0.0.1:3000";
+const TOKEN_ENV: &str = "QUARK_API_TOKEN";
+
+const EXIT_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+#[derive(Debug)]
+struct DaemonArgs {
+    listen: SocketAddr,
+    data_dir: PathBuf,
+    /// 启动前扫码登录
+    login: bool,
+    invert: bool,
+}
+
+/// 命令行里带 --daemon 时不启动窗口
+pub fn requested(args: &[String]) -> bool {
+    args.iter().any(|a| a == "--daemon")
+}
+
+/// 守护进程入口，返回进程退出码
+pub fn main(args: &[String]) -> i32 {
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return 0;
+    }
+    let args = match parse_args(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("错误: {}\n\n{}", e, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+    match tauri::async_runtime::block_on(run(args)) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("[daemon] {}", e);
+            EXIT_ERROR
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<DaemonArgs, String> {
+    let mut listen = None;
+    let mut data_dir = None;
+    let (mut login, mut invert) = (false, false);
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--daemon" => continue,
+            "--login" => {
+                login = true;
+                continue;
+            }
+            "--invert" => {
+                invert = true;
+                continue;
+            }
+            _ => {}
+        }
+        // 同时支持 --listen addr 与 --listen=addr
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| iter.next().cloned())
+                .ok_or_else(|| format!("{} 缺少参数值", flag))
+        };
+        match flag {
+            "--listen" => {
+                let raw = value()?;
+                let addr = raw
+                    .parse::<SocketAddr>()
+                    .map_err(|_| format!("监听地址格式错误: {}", raw))?;
+                listen = Some(addr);
+            }
+            "--data-dir" => data_dir = Some(PathBuf::from(value()?)),
+            _ => return Err(format!("未知选项: {}", arg)),
+        }
+    }
+    Ok(DaemonArgs {
+        listen: match listen {
+            Some(addr) => addr,
+            None => DEFAULT_LISTEN.parse().expect("默认监听地址"),
+        },
+        data_dir: data_dir.unwrap_or_else(default_data_dir),
+        login,
+        invert,
+    })
+}
+
+fn default_data_dir() -> PathBuf {
+    dirs::data_dir()
+        .map(|dir| dir.join(APP_IDENTIFIER))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// 没有窗口可推送，任务只在结束时写一行日志；进度仍记在下载注册表里供 /api/downloads 查询
+fn log_job(job: &Job) {
+    match job.state {
+        JobState::Done => println!("[queue] 任务完成 {} ({})", job.id, job.filename),
+        JobState::Failed => println!(
+            "[queue] 任务失败 {} ({}): {}",
+            job.id,
+            job.filename,
+            job.error.as_deref().unwrap_or("未知错误")
+        ),
+        JobState::Cancelled => println!("[queue] 任务已取消 {} ({})", job.id, job.filename),
+        _ => {}
+    }
+}
+
+async fn run(args: DaemonArgs) -> Result<(), String> {
+    println!("[daemon] 数据目录: {}", args.data_dir.display());
+    let backend = Backend::open(
+        &args.data_dir,
+        Arc::new(|_: DownloadProgress| {}),
+        Arc::new(log_job),
+    )?;
+    if args.login {
+        qr_login(&backend, args.invert).await?;
+    }
+
+    let token = std::env::var(TOKEN_ENV)
+        .ok()
+        .filter(|t| !t.trim().is_empty());
+    let from_env = token.is_some();
+    let auth = Arc::new(ApiAuth::headless(token));
+    if from_env {
+        println!("[daemon] 使用环境变量 {} 中的访问令牌", TOKEN_ENV);
+    } else {
+        println!("[daemon] 访问令牌: {}", auth.token());
+    }
+    if !args.listen.ip().is_loopback() {
+        println!(
+            "[daemon] 注意: {} 可被其他机器访问，接口为明文 HTTP，请只在可信网络中使用或置于 HTTPS 反向代理之后",
+            args.listen
+        );
+    }
+
+    let listener = tokio::net::TcpListener::bind(args.listen)
+        .await
+        .map_err(|e| format!("监听 {} 失败: {}", args.listen, e))?;
+    println!("[quark-api] 守护进程已启动: http://{}", args.listen);
+    axum::serve(
+        listener,
+        api_server::create_router(Arc::clone(&backend), auth),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .map_err(|e| format!("HTTP 服务异常: {}", e))?;
+
+    println!("[daemon] 正在退出，保存未完成的下载");
+    backend.shutdown().await;
+    println!("[daemon] 已退出");
+    Ok(())
+}
+
+/// 在终端显示登录二维码，确认后把 Cookie 存成数据目录下的会话，并打印会话 ID 供 HTTP 接口使用
+async fn qr_login(backend: &Backend, invert: bool) -> Result<(), String> {
+    let api = backend.clients.api();
+    let login = QrLogin::start(&api).await?;
+    println!(
+        "[daemon] 请使用夸克 APP 扫描二维码登录（{} 秒内有效）：\n",
+        QR_LIFETIME.as_secs()
+    );
+    println!("{}", login.render_terminal(invert)?);
+    println!("[daemon] 二维码显示不全时请放大终端窗口；扫不出时试试 --invert\n");
+    let jar = login
+        .wait(&api, |state| println!("[daemon] {}", state.describe()))
+        .await?;
+
+    let result = quark_client::fetch_account_info_by_cookie(&api, &jar.header()).await?;
+    if !quark_client::can_read_account_info(result.status_code, &result.info) {
+        return Err("扫码登录后 Cookie 无效".into());
+    }
+    let session = backend.sessions.create(jar)?;
+    println!("[daemon] 已登录: {}", result.info.nickname());
+    println!("[daemon] 会话 ID: {}", session);
+    Ok(())
+}
+
+/// Ctrl+C，或 Unix 上的 SIGTERM（systemd / docker stop）
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn defaults_to_loopback() {
+        let parsed = parse_args(&args(&["--daemon"])).unwrap();
+        assert_eq!(parsed.listen, DEFAULT_LISTEN.parse().unwrap());
+    }
+
+    #[test]
+    fn accepts_both_flag_forms() {
+        let parsed = parse_args(&args(&[
+            "--daemon",
+            "--listen",
+            "0.0.0.0:8080",
+            "--data-dir=/srv/quark",
+        ]))
+        .unwrap();
+        assert_eq!(parsed.listen, "0.0.0.0:8080".parse().unwrap());
+        assert_eq!(parsed.data_dir, PathBuf::from("/srv/quark"));
+    }
+
+    #[test]
+    fn login_switches_take_no_value() {
+        let parsed = parse_args(&args(&[
+            "--daemon",
+            "--login",
+            "--invert",
+            "--listen",
+            "0.0.0.0:8080",
+        ]))
+        .unwrap();
+        assert!(parsed.login && parsed.invert);
+        assert_eq!(parsed.listen, "0.0.0.0:8080".parse().unwrap());
+        assert!(!parse_args(&args(&["--daemon"])).unwrap().login);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(parse_args(&args(&["--daemon", "--listen", "localhost"])).is_err());
+        assert!(parse_args(&args(&["--daemon", "--listen"])).is_err());
+        assert!(parse_args(&args(&["--daemon", "--port", "3000"])).is_err());
+    }
+}
@@ -0,0 +1,343 @@
+use crate::queue::JobState;
+use crate::settings::ConflictPolicy;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// 一条下载记录：对外查询时返回的内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    pub id: String,
+    pub pwd_id: String,
+    pub fid: String,
+    pub filename: String,
+    pub size: u64,
+    pub save_path: Option<String>,
+    pub state: JobState,
+    pub error: Option<String>,
+    /// 毫秒时间戳
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub finished_at: Option<u64>,
+}
+
+impl HistoryRecord {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.state,
+            JobState::Done | JobState::Failed | JobState::Cancelled
+        )
+    }
+}
+
+/// 重启后恢复未完成任务所需的信息，任务结束后不再保留
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingSource {
+    /// 登录会话 ID，恢复任务时再从会话中取 Cookie
+    #[serde(default)]
+    pub session: String,
+    /// 旧版本日志直接存了 Cookie 原文：只读不写，载入后迁移成会话
+    #[serde(default, skip_serializing)]
+    pub cookie: Option<String>,
+    pub stoken: String,
+    pub share_fid_token: String,
+    /// 保存目录与本地文件名；旧日志中没有这两项
+    #[serde(default)]
+    pub target_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub save_name: Option<String>,
+    pub thread_count: usize,
+    pub max_retries: u32,
+    #[serde(default)]
+    pub conflict_policy: ConflictPolicy,
+}
+
+/// 日志中的一行：同一任务的多行以最后一行为准
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    #[serde(flatten)]
+    pub record: HistoryRecord,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending: Option<PendingSource>,
+}
+
+/// 查询 / 清理条件，字段均可省略
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HistoryFilter {
+    pub state: Option<JobState>,
+    pub pwd_id: Option<String>,
+    /// 文件名关键字
+    pub keyword: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl HistoryFilter {
+    fn matches(&self, record: &HistoryRecord) -> bool {
+        if self.state.is_some_and(|s| s != record.state) {
+            return false;
+        }
+        if self.pwd_id.as_ref().is_some_and(|p| *p != record.pwd_id) {
+            return false;
+        }
+        if let Some(keyword) = self.keyword.as_ref().filter(|k| !k.is_empty()) {
+            return record
+                .filename
+                .to_lowercase()
+                .contains(&keyword.to_lowercase());
+        }
+        true
+    }
+}
+
+/// 下载历史：app 数据目录下的 JSON Lines 日志。
+/// 每次状态变化追加一行，启动时按任务 ID 合并并压缩重写。
+pub struct HistoryStore {
+    path: PathBuf,
+    entries: Mutex<Vec<JournalEntry>>,
+}
+
+impl HistoryStore {
+    pub fn open(path: PathBuf) -> Self {
+        let entries = load_journal(&path);
+        let store = Self {
+            path,
+            entries: Mutex::new(entries),
+        };
+        if let Ok(entries) = store.entries.lock() {
+            if let Err(e) = rewrite_journal(&store.path, &entries) {
+                println!("[history] 压缩历史记录失败: {}", e);
+            }
+            println!(
+                "[history] 已载入 {} 条下载记录: {:?}",
+                entries.len(),
+                store.path
+            );
+        }
+        store
+    }
+
+    /// 写入或更新一条记录
+    pub fn record(&self, entry: JournalEntry) {
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+        if let Err(e) = append_journal(&self.path, &entry) {
+            println!("[history] 写入历史记录失败: {}", e);
+        }
+        match entries.iter_mut().find(|e| e.record.id == entry.record.id) {
+            Some(existing) => *existing = entry,
+            None => entries.push(entry),
+        }
+    }
+
+    /// 按条件查询，最新的在前
+    pub fn query(&self, filter: &HistoryFilter) -> Vec<HistoryRecord> {
+        let Ok(entries) = self.entries.lock() else {
+            return Vec::new();
+        };
+        entries
+            .iter()
+            .rev()
+            .map(|e| &e.record)
+            .filter(|r| filter.matches(r))
+            .take(filter.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+
+    /// 删除符合条件的已结束记录，未完成的任务不受影响；返回删除数量
+    pub fn clear(&self, filter: &HistoryFilter) -> Result<usize, String> {
+        let mut entries = self
+            .entries
+            .lock()
+            .map_err(|_| "历史记录被占用".to_string())?;
+        let before = entries.len();
+        entries.retain(|e| !(e.record.is_finished() && filter.matches(&e.record)));
+        let removed = before - entries.len();
+        rewrite_journal(&self.path, &entries)?;
+        Ok(removed)
+    }
+
+    /// 上次退出时尚未结束的任务
+    pub fn pending(&self) -> Vec<JournalEntry> {
+        let Ok(entries) = self.entries.lock() else {
+            return Vec::new();
+        };
+        entries
+            .iter()
+            .filter(|e| !e.record.is_finished() && e.pending.is_some())
+            .cloned()
+            .collect()
+    }
+}
+
+pub fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// 读取日志并按任务 ID 合并，保持首次出现的顺序；无法解析的行直接跳过
+fn load_journal(path: &Path) -> Vec<JournalEntry> {
+    let Ok(raw) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<JournalEntry> = Vec::new();
+    for line in raw.lines().filter(|l| !l.trim().is_empty()) {
+        let Ok(entry) = serde_json::from_str::<JournalEntry>(line) else {
+            continue;
+        };
+        match entries.iter_mut().find(|e| e.record.id == entry.record.id) {
+            Some(existing) => *existing = entry,
+            None => entries.push(entry),
+        }
+    }
+    entries
+}
+
+fn append_journal(path: &Path, entry: &JournalEntry) -> Result<(), String> {
+    let line = serde_json::to_string(entry).map_err(|e| format!("序列化历史记录失败: {}", e))?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("打开历史记录失败: {}", e))?;
+    writeln!(file, "{}", line).map_err(|e| format!("写入历史记录失败: {}", e))
+}
+
+/// 原子重写整个日志（先写临时文件再 rename）
+fn rewrite_journal(path: &Path, entries: &[JournalEntry]) -> Result<(), String> {
+    let mut raw = String::new();
+    for entry in entries {
+        let line =
+            serde_json::to_string(entry).map_err(|e| format!("序列化历史记录失败: {}", e))?;
+        raw.push_str(&line);
+        raw.push('\n');
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, raw).map_err(|e| format!("写入历史记录失败: {}", e))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("写入历史记录失败: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, filename: &str, state: JobState) -> JournalEntry {
+        let finished = matches!(
+            state,
+            JobState::Done | JobState::Failed | JobState::Cancelled
+        );
+        JournalEntry {
+            record: HistoryRecord {
+                id: id.into(),
+                pwd_id: "pwd".into(),
+                fid: format!("fid-{}", id),
+                filename: filename.into(),
+                size: 1,
+                save_path: None,
+                state,
+                error: None,
+                created_at: 1,
+                updated_at: 1,
+                finished_at: finished.then_some(2),
+            },
+            pending: (!finished).then(|| PendingSource {
+                session: "session-1".into(),
+                cookie: None,
+                stoken: "stoken".into(),
+                share_fid_token: "token".into(),
+                target_dir: None,
+                save_name: None,
+                thread_count: 4,
+                max_retries: 3,
+                conflict_policy: ConflictPolicy::Rename,
+            }),
+        }
+    }
+
+    fn store() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        (dir, path)
+    }
+
+    #[test]
+    fn records_survive_reopen() {
+        let (_dir, path) = store();
+        let history = HistoryStore::open(path.clone());
+        history.record(entry("a", "a.mkv", JobState::Queued));
+        history.record(entry("b", "b.mkv", JobState::Queued));
+        history.record(entry("a", "a.mkv", JobState::Done));
+
+        let reopened = HistoryStore::open(path);
+        let records = reopened.query(&HistoryFilter::default());
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].id, "b");
+        assert_eq!(records[1].state, JobState::Done);
+        let pending = reopened.pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].pending.as_ref().unwrap().session, "session-1");
+    }
+
+    #[test]
+    fn filter_and_clear_skip_unfinished_jobs() {
+        let (_dir, path) = store();
+        let history = HistoryStore::open(path.clone());
+        history.record(entry("a", "Movie.mkv", JobState::Done));
+        history.record(entry("b", "movie-2.mkv", JobState::Failed));
+        history.record(entry("c", "movie-3.mkv", JobState::Downloading));
+        history.record(entry("d", "notes.txt", JobState::Done));
+
+        let keyword = HistoryFilter {
+            keyword: Some("MOVIE".into()),
+            ..Default::default()
+        };
+        assert_eq!(history.query(&keyword).len(), 3);
+        let failed = HistoryFilter {
+            state: Some(JobState::Failed),
+            ..Default::default()
+        };
+        assert_eq!(history.query(&failed)[0].id, "b");
+
+        assert_eq!(history.clear(&keyword).unwrap(), 2);
+        let left: Vec<String> = HistoryStore::open(path)
+            .query(&HistoryFilter::default())
+            .into_iter()
+            .map(|r| r.id)
+            .collect();
+        assert_eq!(left, ["d", "c"]);
+    }
+
+    #[test]
+    fn legacy_cookie_is_loaded_but_not_written_back() {
+        let (_dir, path) = store();
+        let mut line = serde_json::to_value(entry("a", "a.mkv", JobState::Queued)).unwrap();
+        let pending = line["pending"].as_object_mut().unwrap();
+        pending.remove("session");
+        pending.insert("cookie".into(), "__puus=secret".into());
+        std::fs::write(&path, format!("{}\n", line)).unwrap();
+
+        let history = HistoryStore::open(path.clone());
+        let pending = history.pending()[0].pending.clone().unwrap();
+        assert_eq!(pending.cookie.as_deref(), Some("__puus=secret"));
+        assert!(pending.session.is_empty());
+        // 载入时的压缩重写不再带出 Cookie 原文
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("__puus"));
+    }
+
+    #[test]
+    fn truncated_last_line_is_skipped() {
+        let (_dir, path) = store();
+        let line = serde_json::to_string(&entry("a", "a.mkv", JobState::Done)).unwrap();
+        std::fs::write(&path, format!("{}\n{}", line, &line[..line.len() / 2])).unwrap();
+
+        let history = HistoryStore::open(path);
+        let records = history.query(&HistoryFilter::default());
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].id, "a");
+    }
+}
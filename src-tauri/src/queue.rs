@@ -0,0 +1,606 @@
+use crate::backend::Backend;
+use crate::clients::ApiClient;
+use crate::downloader::{self, DownloadCtx};
+use crate::history::{self, HistoryRecord, JournalEntry, PendingSource};
+use crate::limiter::ConcurrencyLimit;
+use crate::quark_client;
+use crate::quark_types::DownloadItem;
+use crate::sanitize;
+use crate::session::CookieJar;
+use crate::settings::ConflictPolicy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+pub const DEFAULT_FILE_CONCURRENCY: usize = 5;
+pub const DEFAULT_MAX_CONNECTIONS: usize = 64;
+
+/// 任务状态：排队 → 解析（转存 + 取链接）→ 下载 → 校验 → 完成 / 失败；未结束前可被取消
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Resolving,
+    Downloading,
+    Verifying,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+/// 前端提交的分享文件
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShareFile {
+    pub fid: String,
+    pub share_fid_token: String,
+    pub file_name: String,
+    #[serde(default)]
+    pub size: u64,
+    /// 分享内的相对路径（含文件名），用于在本地重建目录结构
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnqueueRequest {
+    /// 登录会话 ID，随任务保存；Cookie 在任务开始时才从会话取出，能用上会话续期后的最新值
+    pub session: String,
+    pub pwd_id: String,
+    pub stoken: String,
+    pub files: Vec<ShareFile>,
+    /// 下载根目录，省略时用系统下载目录
+    #[serde(default)]
+    pub save_dir: Option<String>,
+    /// 给出时在根目录下多建一级以分享标题命名的目录
+    #[serde(default)]
+    pub share_title: Option<String>,
+    /// 省略时用全局设置
+    #[serde(default)]
+    pub conflict_policy: Option<ConflictPolicy>,
+    pub thread_count: Option<usize>,
+    pub retry_count: Option<u32>,
+}
+
+/// 一个文件的完整下载任务，状态变化通过 job-update 事件推送
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: String,
+    pub pwd_id: String,
+    pub fid: String,
+    pub filename: String,
+    pub size: u64,
+    pub state: JobState,
+    pub error: Option<String>,
+    /// 保存路径，开始下载时确定
+    pub path: Option<String>,
+    /// 毫秒时间戳
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub finished_at: Option<u64>,
+    #[serde(skip)]
+    share_fid_token: String,
+    /// 保存目录（已按分享目录结构展开）
+    #[serde(skip)]
+    target_dir: PathBuf,
+    /// 清洗后的本地文件名
+    #[serde(skip)]
+    save_name: String,
+    #[serde(skip)]
+    source: Arc<JobSource>,
+}
+
+/// 同一批提交共享的分享信息与下载参数
+#[derive(Debug)]
+struct JobSource {
+    session: String,
+    pwd_id: String,
+    stoken: String,
+    thread_count: usize,
+    max_retries: u32,
+    conflict: ConflictPolicy,
+}
+
+impl Job {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.state,
+            JobState::Done | JobState::Failed | JobState::Cancelled
+        )
+    }
+
+    /// 对应的历史日志行；未结束的任务附带重启后恢复所需的信息
+    fn journal_entry(&self) -> JournalEntry {
+        let pending = (!self.is_finished()).then(|| PendingSource {
+            session: self.source.session.clone(),
+            cookie: None,
+            stoken: self.source.stoken.clone(),
+            share_fid_token: self.share_fid_token.clone(),
+            target_dir: Some(self.target_dir.clone()),
+            save_name: Some(self.save_name.clone()),
+            thread_count: self.source.thread_count,
+            max_retries: self.source.max_retries,
+            conflict_policy: self.source.conflict,
+        });
+        JournalEntry {
+            record: HistoryRecord {
+                id: self.id.clone(),
+                pwd_id: self.pwd_id.clone(),
+                fid: self.fid.clone(),
+                filename: self.filename.clone(),
+                size: self.size,
+                save_path: self.path.clone(),
+                state: self.state,
+                error: self.error.clone(),
+                created_at: self.created_at,
+                updated_at: self.updated_at,
+                finished_at: self.finished_at,
+            },
+            pending,
+        }
+    }
+}
+
+/// 任务状态变化的接收方：桌面应用转发为 job-update 事件，守护进程只写日志
+pub trait JobReporter: Send + Sync {
+    fn report(&self, job: &Job);
+}
+
+impl<F> JobReporter for F
+where
+    F: Fn(&Job) + Send + Sync,
+{
+    fn report(&self, job: &Job) {
+        self(job)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct QueueLimits {
+    /// 同时处理的文件数
+    pub file_concurrency: usize,
+    /// 所有下载合计的最大连接数
+    pub max_connections: usize,
+}
+
+/// 后端下载队列：任务在 Rust 侧运行，与 webview 的生命周期无关。
+/// 文件级并发与全局连接数分别由两道闸门控制。
+pub struct JobQueue {
+    jobs: Mutex<Vec<Job>>,
+    file_slots: ConcurrencyLimit,
+    connections: Arc<ConcurrencyLimit>,
+    /// 退出中：不再开始新任务，被中断的任务也不记为失败
+    closing: AtomicBool,
+    /// 已拿到文件槽位、尚未结束的任务数
+    running: AtomicUsize,
+}
+
+/// 任务结束（包括中途返回）时把运行计数减一
+struct RunningGuard<'a>(&'a AtomicUsize);
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        Self {
+            jobs: Mutex::new(Vec::new()),
+            file_slots: ConcurrencyLimit::new(DEFAULT_FILE_CONCURRENCY),
+            connections: Arc::new(ConcurrencyLimit::new(DEFAULT_MAX_CONNECTIONS)),
+            closing: AtomicBool::new(false),
+            running: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl JobQueue {
+    pub fn limits(&self) -> QueueLimits {
+        QueueLimits {
+            file_concurrency: self.file_slots.limit(),
+            max_connections: self.connections.limit(),
+        }
+    }
+
+    pub fn set_limits(&self, file_concurrency: Option<usize>, max_connections: Option<usize>) {
+        if let Some(n) = file_concurrency {
+            self.file_slots.set_limit(n);
+        }
+        if let Some(n) = max_connections {
+            self.connections.set_limit(n);
+        }
+    }
+
+    /// 全局连接闸门，直接下载（不经队列）时也共享这一上限
+    pub fn connections(&self) -> Arc<ConcurrencyLimit> {
+        Arc::clone(&self.connections)
+    }
+
+    /// 进入退出流程，之后拿到槽位的任务直接放弃，留待下次启动恢复
+    pub fn close(&self) {
+        self.closing.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_closing(&self) -> bool {
+        self.closing.load(Ordering::SeqCst)
+    }
+
+    pub fn running(&self) -> usize {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    pub fn list(&self) -> Vec<Job> {
+        self.jobs
+            .lock()
+            .map(|jobs| jobs.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Job> {
+        self.jobs.lock().ok()?.iter().find(|j| j.id == id).cloned()
+    }
+
+    /// 修改未结束的任务并返回修改后的副本；已结束（含已取消）的任务保持不变
+    fn update(&self, id: &str, f: impl FnOnce(&mut Job)) -> Option<Job> {
+        let mut jobs = self.jobs.lock().ok()?;
+        let job = jobs.iter_mut().find(|j| j.id == id && !j.is_finished())?;
+        f(job);
+        Some(job.clone())
+    }
+
+    /// 取消未结束的任务：排队中的不再开始，下载中的立即中断并删除部分数据。
+    /// 任务不存在或已结束时返回 None
+    pub fn cancel(&self, backend: &Backend, id: &str) -> Option<Job> {
+        let job = transition(backend, id, |j| j.state = JobState::Cancelled)?;
+        if let Some(handle) = backend.registry.get(id) {
+            handle.cancel();
+        }
+        println!("[queue] 取消任务 {} ({})", id, job.filename);
+        Some(job)
+    }
+
+    /// 批量入队并立即开始调度，返回新建的任务；任一文件的相对路径不合法时整批拒绝
+    pub fn enqueue(
+        &self,
+        backend: &Arc<Backend>,
+        request: EnqueueRequest,
+    ) -> Result<Vec<Job>, String> {
+        if self.is_closing() {
+            return Err("服务正在退出，不再接收新任务".into());
+        }
+        let settings = &backend.settings;
+        // 只校验会话存在，Cookie 本身不随任务保存
+        backend.sessions.cookie(&request.session)?;
+        let source = Arc::new(JobSource {
+            session: request.session,
+            pwd_id: request.pwd_id,
+            stoken: request.stoken,
+            thread_count: request.thread_count.unwrap_or(999).clamp(1, 999),
+            max_retries: request
+                .retry_count
+                .unwrap_or(downloader::DEFAULT_SEGMENT_RETRIES)
+                .min(downloader::MAX_SEGMENT_RETRIES),
+            conflict: settings.conflict_policy(request.conflict_policy),
+        });
+
+        let root = settings.download_root(request.save_dir.as_deref());
+        let share_title = request.share_title.as_deref();
+        let now = history::now_millis();
+        let created = request
+            .files
+            .into_iter()
+            .map(|f| {
+                let (target_dir, save_name) =
+                    downloader::share_target(&root, share_title, f.path.as_deref(), &f.file_name)?;
+                Ok(Job {
+                    id: uuid::Uuid::new_v4().to_string()[..8].to_string(),
+                    pwd_id: source.pwd_id.clone(),
+                    fid: f.fid,
+                    filename: f.file_name,
+                    size: f.size,
+                    state: JobState::Queued,
+                    error: None,
+                    path: None,
+                    created_at: now,
+                    updated_at: now,
+                    finished_at: None,
+                    share_fid_token: f.share_fid_token,
+                    target_dir,
+                    save_name,
+                    source: Arc::clone(&source),
+                })
+            })
+            .collect::<Result<Vec<Job>, String>>()?;
+
+        println!("[queue] 新增 {} 个任务", created.len());
+        self.submit(backend, &created);
+        Ok(created)
+    }
+
+    /// 重新排队上次退出时未完成的任务（沿用原任务 ID，部分数据按续传状态继续）
+    pub fn restore(&self, backend: &Arc<Backend>, pending: Vec<JournalEntry>) {
+        let now = history::now_millis();
+        // 旧版本日志里存的是 Cookie 原文，转成会话；同一个 Cookie 只建一个会话
+        let mut migrated: HashMap<String, String> = HashMap::new();
+        let restored: Vec<Job> = pending
+            .into_iter()
+            .filter_map(|entry| {
+                let mut p = entry.pending?;
+                let legacy = p
+                    .cookie
+                    .take()
+                    .filter(|c| p.session.is_empty() && !c.is_empty());
+                if let Some(cookie) = legacy {
+                    p.session = match migrated.get(&cookie) {
+                        Some(session) => session.clone(),
+                        None => {
+                            let session = backend
+                                .sessions
+                                .create(CookieJar::parse(&cookie))
+                                .unwrap_or_else(|e| {
+                                    println!("[queue] 迁移旧任务的登录信息失败: {}", e);
+                                    String::new()
+                                });
+                            migrated.insert(cookie, session.clone());
+                            session
+                        }
+                    };
+                }
+                let r = entry.record;
+                // 旧版本日志没有保存目录，按原先的规则落到系统下载目录
+                let target_dir = p
+                    .target_dir
+                    .unwrap_or_else(downloader::default_download_dir);
+                let save_name = p
+                    .save_name
+                    .unwrap_or_else(|| sanitize::sanitize_filename(&r.filename));
+                Some(Job {
+                    id: r.id,
+                    fid: r.fid,
+                    filename: r.filename,
+                    size: r.size,
+                    state: JobState::Queued,
+                    error: None,
+                    path: r.save_path,
+                    created_at: r.created_at,
+                    updated_at: now,
+                    finished_at: None,
+                    share_fid_token: p.share_fid_token,
+                    target_dir,
+                    save_name,
+                    source: Arc::new(JobSource {
+                        session: p.session,
+                        pwd_id: r.pwd_id.clone(),
+                        stoken: p.stoken,
+                        thread_count: p.thread_count,
+                        max_retries: p.max_retries,
+                        conflict: p.conflict_policy,
+                    }),
+                    pwd_id: r.pwd_id,
+                })
+            })
+            .collect();
+
+        if restored.is_empty() {
+            return;
+        }
+        println!("[queue] 恢复 {} 个未完成的任务", restored.len());
+        self.submit(backend, &restored);
+    }
+
+    /// 加入任务列表、写入历史并开始调度
+    fn submit(&self, backend: &Arc<Backend>, created: &[Job]) {
+        if let Ok(mut jobs) = self.jobs.lock() {
+            jobs.extend(created.iter().cloned());
+        }
+        for job in created {
+            backend.history.record(job.journal_entry());
+            backend.jobs.report(job);
+            tauri::async_runtime::spawn(run_job(Arc::clone(backend), job.id.clone()));
+        }
+    }
+}
+
+/// 更新任务状态，写入历史并推送事件；已结束的任务不再变化
+fn transition(backend: &Backend, id: &str, f: impl FnOnce(&mut Job)) -> Option<Job> {
+    let job = backend.queue.update(id, |job| {
+        f(job);
+        job.updated_at = history::now_millis();
+        if job.is_finished() {
+            job.finished_at = Some(job.updated_at);
+        }
+    })?;
+    backend.history.record(job.journal_entry());
+    backend.jobs.report(&job);
+    Some(job)
+}
+
+/// 单个任务：等待文件槽位 → 转存并取链接 → 下载 → 校验
+async fn run_job(backend: Arc<Backend>, id: String) {
+    let queue = &backend.queue;
+    let _slot = queue.file_slots.acquire().await;
+    if queue.is_closing() {
+        return;
+    }
+    queue.running.fetch_add(1, Ordering::SeqCst);
+    let _running = RunningGuard(&queue.running);
+    // 排队期间已被取消的任务不再开始
+    let Some(job) = queue.get(&id).filter(|j| !j.is_finished()) else {
+        return;
+    };
+
+    println!("[queue] 开始任务 {} ({})", id, job.filename);
+    let result = process_job(&backend, &job).await;
+    // 退出时被中断的任务保持未结束，历史中的恢复信息留到下次启动
+    if queue.is_closing() && result.is_err() {
+        println!(
+            "[queue] 任务已中断，下次启动时继续 {} ({})",
+            id, job.filename
+        );
+        return;
+    }
+    transition(&backend, &id, |j| match result {
+        Ok(path) => {
+            j.state = JobState::Done;
+            j.path = Some(path);
+        }
+        Err(e) => {
+            println!("[queue] 任务失败 {} ({}): {}", j.id, j.filename, e);
+            j.state = JobState::Failed;
+            j.error = Some(e);
+        }
+    });
+}
+
+async fn process_job(backend: &Backend, job: &Job) -> Result<String, String> {
+    let source = &job.source;
+
+    transition(backend, &job.id, |j| j.state = JobState::Resolving);
+    let cookie = backend.sessions.cookie(&source.session)?;
+    let clients = &backend.clients;
+    let link = resolve_download_link(&clients.api(), &cookie, job).await?;
+    if backend.queue.is_closing() {
+        return Err("服务正在退出".into());
+    }
+
+    tokio::fs::create_dir_all(&job.target_dir)
+        .await
+        .map_err(|e| format!("创建保存目录失败: {}", e))?;
+    let (save_path, state_path) =
+        downloader::prepare_target(&job.target_dir, &job.save_name, source.conflict).await;
+    transition(backend, &job.id, |j| {
+        j.state = JobState::Downloading;
+        j.path = Some(save_path.to_string_lossy().to_string());
+    });
+    let registry = &backend.registry;
+    // 任务 ID 同时作为下载 ID，前端据此把 download-progress 与任务对应起来
+    let handle = registry.register(&job.id, &job.filename, save_path.clone());
+    // 注册之前刚被取消的任务，取消时还找不到这个下载，在这里补上
+    if backend.queue.get(&job.id).is_none_or(|j| j.is_finished()) {
+        handle.cancel();
+    }
+    let ctx = DownloadCtx {
+        progress: Arc::clone(&backend.progress),
+        client: clients.cdn(),
+        cookie,
+        filename: job.filename.clone(),
+        fid: job.fid.clone(),
+        save_path,
+        state_path,
+        handle,
+        download_id: job.id.clone(),
+        thread_count: source.thread_count,
+        max_retries: source.max_retries,
+        connections: backend.queue.connections(),
+        throttle: backend.bandwidth.throttle(),
+        conflict: source.conflict,
+        remote_md5: downloader::normalize_md5(&link.md5),
+    };
+    let result = downloader::do_download(ctx, link.download_url).await;
+    registry.remove(&job.id);
+    let result = result?;
+
+    transition(backend, &job.id, |j| j.state = JobState::Verifying);
+    let path = result
+        .get("path")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string();
+    verify_size(&path, job.size).await?;
+    Ok(path)
+}
+
+/// 转存到网盘 → 获取 CDN 链接 → 立即删除转存文件。
+/// 获取链接失败时同样会清理已转存的文件。
+async fn resolve_download_link(
+    client: &ApiClient,
+    cookie: &str,
+    job: &Job,
+) -> Result<DownloadItem, String> {
+    let source = &job.source;
+    let saved = quark_client::save_to_drive(
+        client,
+        cookie,
+        &source.pwd_id,
+        &source.stoken,
+        std::slice::from_ref(&job.fid),
+        std::slice::from_ref(&job.share_fid_token),
+    )
+    .await?;
+
+    let link = quark_client::get_download_link(client, cookie, &saved.fids()[0]).await;
+    if let Err(e) = saved.cleanup().await {
+        println!("[queue] 转存清理失败 ({}): {}", job.filename, e);
+    }
+    Ok(link?)
+}
+
+/// 校验落盘大小与分享列表中的大小一致（列表未给出大小时跳过）
+async fn verify_size(path: &str, expected: u64) -> Result<(), String> {
+    if expected == 0 {
+        return Ok(());
+    }
+    let actual = tokio::fs::metadata(path)
+        .await
+        .map_err(|e| format!("校验失败: {}", e))?
+        .len();
+    if actual != expected {
+        return Err(format!(
+            "校验失败: 文件大小 {} 与预期 {} 不符",
+            actual, expected
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quark_core::progress::DownloadProgress;
+
+    #[tokio::test]
+    async fn restores_pending_jobs_and_migrates_legacy_cookies() {
+        let dir = tempfile::tempdir().unwrap();
+        // 旧版本写下的两条未完成任务：共用同一个 Cookie，还没有 session 字段
+        let lines: String = ["job-1", "job-2"]
+            .iter()
+            .map(|id| {
+                serde_json::json!({
+                    "id": id, "pwd_id": "pwd", "fid": format!("fid-{}", id), "filename": "a.mkv",
+                    "size": 1, "save_path": null, "state": "downloading", "error": null,
+                    "created_at": 1, "updated_at": 1, "finished_at": null,
+                    "pending": {
+                        "cookie": "__puus=secret", "stoken": "stoken", "share_fid_token": "token",
+                        "thread_count": 4, "max_retries": 3
+                    }
+                })
+                .to_string()
+                    + "\n"
+            })
+            .collect();
+        std::fs::write(dir.path().join("history.jsonl"), lines).unwrap();
+
+        let backend = Backend::open(
+            dir.path(),
+            Arc::new(|_: DownloadProgress| {}),
+            Arc::new(|_: &Job| {}),
+        )
+        .unwrap();
+        backend.queue.close();
+
+        let jobs = backend.queue.list();
+        assert_eq!(
+            jobs.iter().map(|j| j.id.as_str()).collect::<Vec<_>>(),
+            ["job-1", "job-2"]
+        );
+        let session = &jobs[0].source.session;
+        assert!(!session.is_empty());
+        assert_eq!(&jobs[1].source.session, session);
+        assert_eq!(backend.sessions.cookie(session).unwrap(), "__puus=secret");
+        let journal = std::fs::read_to_string(dir.path().join("history.jsonl")).unwrap();
+        assert!(!journal.contains("__puus"));
+    }
+}
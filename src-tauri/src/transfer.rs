@@ -0,0 +1,200 @@
+use crate::backend::Backend;
+use crate::downloader::{self, DownloadCtx};
+use crate::quark_client::{self, SavedFiles};
+use crate::quark_types::DownloadItem;
+use crate::settings::ConflictPolicy;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+
+/// 一次转存下载的参数：分享信息 + 要下载的文件 + 保存目录
+#[derive(Debug, Clone, Deserialize)]
+pub struct TransferRequest {
+    /// 登录会话 ID
+    pub session: String,
+    /// 由会话取出的 Cookie，不接受前端直接传入
+    #[serde(skip)]
+    pub cookie: String,
+    pub pwd_id: String,
+    pub stoken: String,
+    pub fids: Vec<String>,
+    /// 与 fids 一一对应的 share_fid_token
+    pub fid_tokens: Vec<String>,
+    /// 与 fids 一一对应的分享内相对路径，给出时在保存目录下重建目录结构
+    #[serde(default)]
+    pub paths: Vec<String>,
+    /// 保存目录，省略时用系统下载目录
+    pub target_dir: Option<String>,
+    /// 给出时在保存目录下多建一级以分享标题命名的目录
+    #[serde(default)]
+    pub share_title: Option<String>,
+    /// 省略时用全局设置
+    #[serde(default)]
+    pub conflict_policy: Option<ConflictPolicy>,
+    pub thread_count: Option<usize>,
+    pub retry_count: Option<u32>,
+}
+
+/// 单个文件的结果：成功时带保存路径，失败时带错误信息
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferredFile {
+    /// 分享中的 fid，与请求里的 fids 对应
+    pub fid: String,
+    pub filename: String,
+    pub size: u64,
+    pub path: Option<String>,
+    pub error: Option<String>,
+}
+
+/// 转存 → 逐个文件获取直链并走多线程引擎下载 → 删除转存文件。
+/// 无论中途成功与否都会清理转存文件；单个文件下载失败不影响其余文件。
+pub async fn transfer_share_files(
+    backend: &Backend,
+    mut request: TransferRequest,
+) -> Result<Vec<TransferredFile>, String> {
+    request.cookie = backend.sessions.cookie(&request.session)?;
+    if request.fids.is_empty() {
+        return Err("没有可下载的文件".into());
+    }
+    if request.fids.len() != request.fid_tokens.len() {
+        return Err("fids 与 fid_tokens 数量不一致".into());
+    }
+    if !request.paths.is_empty() && request.paths.len() != request.fids.len() {
+        return Err("fids 与 paths 数量不一致".into());
+    }
+
+    let root = backend
+        .settings
+        .download_root(request.target_dir.as_deref());
+
+    let client = backend.clients.api();
+    let saved = quark_client::save_to_drive(
+        &client,
+        &request.cookie,
+        &request.pwd_id,
+        &request.stoken,
+        &request.fids,
+        &request.fid_tokens,
+    )
+    .await?;
+    println!("[transfer] 已转存 {} 个文件", saved.fids().len());
+
+    let result = download_saved(backend, &request, &saved, &root).await;
+    if let Err(e) = saved.cleanup().await {
+        println!("[transfer] 清理转存文件失败: {}", e);
+    }
+    result
+}
+
+async fn download_saved(
+    backend: &Backend,
+    request: &TransferRequest,
+    saved: &SavedFiles,
+    root: &Path,
+) -> Result<Vec<TransferredFile>, String> {
+    let client = backend.clients.api();
+    let mut results = Vec::with_capacity(request.fids.len());
+    // 每个提交的 fid 都有一条结果；paths 与 fids 数量已校验一致
+    for (i, (share_fid, saved_fid)) in saved.pairs().enumerate() {
+        let rel_path = request.paths.get(i).map(String::as_str);
+        // 直链有时效，轮到这个文件时再取，避免排在后面的文件拿到过期链接
+        let link =
+            quark_client::get_download_links(&client, &request.cookie, &[saved_fid.to_string()])
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|links| {
+                    // 目录没有直链
+                    links
+                        .into_iter()
+                        .next()
+                        .ok_or_else(|| "目录不支持直接下载，请选择目录下的文件".to_string())
+                });
+        let item = match link {
+            Ok(item) => item,
+            Err(e) => {
+                println!("[transfer] 获取下载链接失败 ({}): {}", share_fid, e);
+                results.push(TransferredFile {
+                    fid: share_fid.to_string(),
+                    filename: rel_path
+                        .and_then(|p| p.rsplit('/').next())
+                        .unwrap_or_default()
+                        .to_string(),
+                    size: 0,
+                    path: None,
+                    error: Some(e),
+                });
+                continue;
+            }
+        };
+        let outcome = match downloader::share_target(
+            root,
+            request.share_title.as_deref(),
+            rel_path,
+            &item.file_name,
+        ) {
+            Ok((target_dir, save_name)) => {
+                download_one(backend, request, share_fid, &target_dir, &save_name, &item).await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = &outcome {
+            println!("[transfer] 下载失败 ({}): {}", item.file_name, e);
+        }
+        results.push(TransferredFile {
+            fid: share_fid.to_string(),
+            filename: item.file_name,
+            size: item.size,
+            path: outcome.as_ref().ok().cloned(),
+            error: outcome.err(),
+        });
+    }
+    Ok(results)
+}
+
+/// share_fid 是分享中的 fid：每次转存得到的网盘 fid 都不同，续传状态要按分享 fid 对应
+async fn download_one(
+    backend: &Backend,
+    request: &TransferRequest,
+    share_fid: &str,
+    target_dir: &Path,
+    save_name: &str,
+    item: &DownloadItem,
+) -> Result<String, String> {
+    tokio::fs::create_dir_all(target_dir)
+        .await
+        .map_err(|e| format!("创建保存目录失败: {}", e))?;
+    let conflict = backend.settings.conflict_policy(request.conflict_policy);
+    let (save_path, state_path) = downloader::prepare_target(target_dir, save_name, conflict).await;
+
+    let registry = &backend.registry;
+    let download_id = uuid::Uuid::new_v4().to_string()[..8].to_string();
+    let handle = registry.register(&download_id, &item.file_name, save_path.clone());
+    let ctx = DownloadCtx {
+        progress: Arc::clone(&backend.progress),
+        client: backend.clients.cdn(),
+        cookie: request.cookie.clone(),
+        filename: item.file_name.clone(),
+        fid: share_fid.to_string(),
+        save_path,
+        state_path,
+        handle,
+        download_id: download_id.clone(),
+        thread_count: request.thread_count.unwrap_or(999).clamp(1, 999),
+        max_retries: request
+            .retry_count
+            .unwrap_or(downloader::DEFAULT_SEGMENT_RETRIES)
+            .min(downloader::MAX_SEGMENT_RETRIES),
+        connections: backend.queue.connections(),
+        throttle: backend.bandwidth.throttle(),
+        conflict,
+        remote_md5: downloader::normalize_md5(&item.md5),
+    };
+    let result = downloader::do_download(ctx, item.download_url.clone()).await;
+    registry.remove(&download_id);
+
+    Ok(result?
+        .get("path")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string())
+}